@@ -1,91 +1,45 @@
 use xtra::prelude::*;
 
-use sp_runtime::traits::Block as BlockT;
+use archive_kafka::{KafkaConfig, KafkaError, KafkaProducer};
 
-use archive_kafka::{payload::*, KafkaConfig, KafkaError, KafkaProducer};
+use crate::message::{Die, Flush, PublishMessage};
 
-use crate::message::{
-    BatchBlockMessage, BlockMessage, Die, FinalizedBlockMessage, MetadataMessage,
-};
-
-pub struct KafkaActor<Block: BlockT> {
+pub struct KafkaActor {
     producer: KafkaProducer,
-    _marker: std::marker::PhantomData<Block>,
 }
 
-impl<B: BlockT> KafkaActor<B> {
+impl KafkaActor {
     pub fn new(config: KafkaConfig) -> Result<Self, KafkaError> {
         let producer = KafkaProducer::new(config)?;
-        Ok(Self {
-            producer,
-            _marker: std::marker::PhantomData,
-        })
-    }
-}
-
-#[async_trait::async_trait]
-impl<Block: BlockT> Actor for KafkaActor<Block> {}
-
-#[async_trait::async_trait]
-impl<Block: BlockT> Handler<MetadataMessage<Block>> for KafkaActor<Block> {
-    async fn handle(
-        &mut self,
-        message: MetadataMessage<Block>,
-        _: &mut Context<Self>,
-    ) -> <MetadataMessage<Block> as Message>::Result {
-        let payload = MetadataPayload::from(message);
-        if let Err(err) = self.producer.send(payload).await {
-            log::error!(target: "actor", "{}", err);
-        }
+        Ok(Self { producer })
     }
 }
 
 #[async_trait::async_trait]
-impl<Block: BlockT> Handler<BlockMessage<Block>> for KafkaActor<Block> {
-    async fn handle(
-        &mut self,
-        message: BlockMessage<Block>,
-        _: &mut Context<Self>,
-    ) -> <BlockMessage<Block> as Message>::Result {
-        let payload = BlockPayload::from(message);
-        if let Err(err) = self.producer.send(payload).await {
-            log::error!(target: "actor", "{}", err);
-        }
-    }
-}
+impl Actor for KafkaActor {}
 
 #[async_trait::async_trait]
-impl<Block: BlockT> Handler<BatchBlockMessage<Block>> for KafkaActor<Block> {
+impl Handler<PublishMessage> for KafkaActor {
     async fn handle(
         &mut self,
-        message: BatchBlockMessage<Block>,
+        message: PublishMessage,
         _: &mut Context<Self>,
-    ) -> <BatchBlockMessage<Block> as Message>::Result {
-        for message in message.into_inner() {
-            let payload = BlockPayload::from(message);
-            if let Err(err) = self.producer.send(payload).await {
-                log::error!(target: "actor", "{}", err);
-            }
-        }
+    ) -> <PublishMessage as Message>::Result {
+        log::debug!(target: "actor", "Publish outbox #{} into kafka", message.id);
+        self.producer.send(message.record).await?;
+        Ok(())
     }
 }
 
 #[async_trait::async_trait]
-impl<Block: BlockT> Handler<FinalizedBlockMessage<Block>> for KafkaActor<Block> {
-    async fn handle(
-        &mut self,
-        message: FinalizedBlockMessage<Block>,
-        _: &mut Context<Self>,
-    ) -> <FinalizedBlockMessage<Block> as Message>::Result {
-        let payload = FinalizedBlockPayload::from(message);
-        if let Err(err) = self.producer.send(payload).await {
-            log::error!(target: "actor", "{}", err);
-        }
+impl Handler<Flush> for KafkaActor {
+    async fn handle(&mut self, _: Flush, _: &mut Context<Self>) -> <Flush as Message>::Result {
+        log::info!(target: "actor", "Flushed Kafka Actor");
     }
 }
 
 #[async_trait::async_trait]
-impl<Block: BlockT> Handler<Die> for KafkaActor<Block> {
+impl Handler<Die> for KafkaActor {
     async fn handle(&mut self, _message: Die, ctx: &mut Context<Self>) -> <Die as Message>::Result {
         log::info!(target: "actor", "Stopping Kafka Actor");
         ctx.stop();
@@ -1,178 +1,106 @@
 pub mod kafka;
+mod relay;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use futures::{future, FutureExt};
-use xtra::{prelude::*, Disconnected};
+use tokio::sync::Notify;
+use xtra::{prelude::*, spawn::TokioGlobalSpawnExt, Disconnected};
 
-use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
+use archive_kafka::PayloadRecord;
+use archive_postgres::{OutboxModel, PostgresDb};
 
-use crate::message::{
-    BatchBlockMessage, BlockMessage, Die, FinalizedBlockMessage, MetadataMessage,
+use self::relay::RelayActor;
+use crate::{
+    config::RelayConfig,
+    error::ActorError,
+    message::{Die, Flush, PublishMessage},
 };
 
-pub trait DispatchActor<Block: BlockT>:
-    Actor
-    + Handler<MetadataMessage<Block>>
-    + Handler<BlockMessage<Block>>
-    + Handler<BatchBlockMessage<Block>>
-    + Handler<FinalizedBlockMessage<Block>>
-    + Handler<Die>
-{
-}
-
-impl<Block, T> DispatchActor<Block> for T
-where
-    Block: BlockT,
-    T: Actor
-        + Handler<MetadataMessage<Block>>
-        + Handler<BlockMessage<Block>>
-        + Handler<BatchBlockMessage<Block>>
-        + Handler<FinalizedBlockMessage<Block>>
-        + Handler<Die>,
-{
-}
+/// The sink publishes the outbox records, it returns error when a record fails to be published,
+/// and the record is published again later.
+pub trait DispatchActor: Actor + Handler<PublishMessage> + Handler<Flush> + Handler<Die> {}
 
-type MessageChannelMap<M> = HashMap<String, Box<dyn StrongMessageChannel<M>>>;
+impl<T> DispatchActor for T where T: Actor + Handler<PublishMessage> + Handler<Flush> + Handler<Die> {}
 
-pub struct Dispatcher<Block: BlockT> {
-    metadata: MessageChannelMap<MetadataMessage<Block>>,
-    block: MessageChannelMap<BlockMessage<Block>>,
-    blocks: MessageChannelMap<BatchBlockMessage<Block>>,
-    finalized_block: MessageChannelMap<FinalizedBlockMessage<Block>>,
-    die: MessageChannelMap<Die>,
+/// The payloads to the sinks are written into the outbox within the same transaction as the data,
+/// and every sink is relayed from the outbox by its own relay actor, which keeps the offset of the sink.
+///
+/// The outbox rows are pruned once all the configured sinks have published them. A sink added
+/// later starts from the oldest retained row, the blocks before it can be published to it by
+/// backfilling them, which publishes them to the other sinks again too.
+#[derive(Clone)]
+pub struct Dispatcher {
+    db: PostgresDb,
+    config: RelayConfig,
+    relays: HashMap<String, (Address<RelayActor>, Arc<Notify>)>,
 }
 
-impl<Block: BlockT> Dispatcher<Block> {
-    pub fn new() -> Self {
-        Self {
-            metadata: HashMap::new(),
-            block: HashMap::new(),
-            blocks: HashMap::new(),
-            finalized_block: HashMap::new(),
-            die: HashMap::new(),
-        }
+impl Dispatcher {
+    /// Registers the offsets of `sinks` before any relay is spawned, so that no relay prunes
+    /// the outbox rows that the others haven't published, the offsets of the sinks that are
+    /// no longer configured are removed, otherwise they would stop the pruning forever.
+    pub async fn new(
+        db: PostgresDb,
+        config: RelayConfig,
+        sinks: &[&str],
+    ) -> Result<Self, ActorError> {
+        db.register_outbox_sinks(sinks).await?;
+        Ok(Self {
+            db,
+            config,
+            relays: HashMap::new(),
+        })
     }
 
-    pub fn add<D: DispatchActor<Block>>(
+    /// Spawns the relay of the sink, whose name must have been registered by `new`.
+    pub fn add<D: DispatchActor>(
         &mut self,
         name: impl Into<String>,
         addr: Address<D>,
     ) -> &mut Self {
         let name = name.into();
-        self.metadata.insert(name.clone(), Box::new(addr.clone()));
-        self.block.insert(name.clone(), Box::new(addr.clone()));
-        self.blocks.insert(name.clone(), Box::new(addr.clone()));
-        self.finalized_block
-            .insert(name.clone(), Box::new(addr.clone()));
-        self.die.insert(name.clone(), Box::new(addr));
+        let notify = Arc::new(Notify::new());
+        let relay = RelayActor::new(
+            name.clone(),
+            self.db.clone(),
+            self.config,
+            notify.clone(),
+            addr,
+        )
+        .create(None)
+        .spawn_global();
+        log::info!(target: "actor", "Spawn Relay Actor of `{}`", name);
+        self.relays.insert(name, (relay, notify));
         self
     }
 
-    pub async fn dispatch_metadata(
-        &self,
-        message: MetadataMessage<Block>,
-    ) -> Result<(), Disconnected> {
-        let results = future::join_all(self.metadata.iter().map(|(name, dispatcher)| {
-            dispatcher
-                .send(message.clone())
-                .then(move |result| future::ready((name.clone(), result)))
-        }))
-        .await;
-        for (name, result) in results {
-            match result {
-                Ok(_) => log::debug!(
-                    target: "actor",
-                    "Dispatch `Metadata` message into `{}`, version = {}",
-                    name, message.version
-                ),
-                Err(err) => log::error!(
-                    target: "actor",
-                    "Failed to dispatch `Metadata` message into `{}`, version = {}: {}",
-                    name, message.version, err
-                ),
-            }
-        }
-        Ok(())
-    }
-
-    pub async fn dispatch_block(&self, message: BlockMessage<Block>) -> Result<(), Disconnected> {
-        let results = future::join_all(self.block.iter().map(|(name, dispatcher)| {
-            dispatcher
-                .send(message.clone())
-                .then(move |result| future::ready((name.clone(), result)))
-        }))
-        .await;
-        for (name, result) in results {
-            match result {
-                Ok(_) => log::debug!(
-                target: "actor",
-                "Dispatch `Block` message into `{}`, height = {}",
-                name, message.inner.block.header().number()
-                ),
-                Err(err) => log::error!(
-                    target: "actor",
-                    "Failed to dispatch `Block` message into `{}`, height = {}: {}",
-                    name, message.inner.block.header().number(), err
-                ),
-            }
-        }
-        Ok(())
+    /// Returns true if all the relays are running.
+    pub fn is_connected(&self) -> bool {
+        self.relays.values().all(|(relay, _)| relay.is_connected())
     }
 
-    pub async fn dispatch_batch_block(
-        &self,
-        message: BatchBlockMessage<Block>,
-    ) -> Result<(), Disconnected> {
-        let results = future::join_all(self.blocks.iter().map(|(name, dispatcher)| {
-            dispatcher
-                .send(message.clone())
-                .then(move |result| future::ready((name.clone(), result)))
-        }))
-        .await;
-        for (name, result) in results {
-            match result {
-                Ok(_) => log::debug!(
-                    target: "actor",
-                    "Dispatch `BatchBlock` message into `{}`, height = [{:?}~{:?}]",
-                    name,
-                    message.inner().first().map(|block| block.inner.block.header().number()),
-                    message.inner().last().map(|block| block.inner.block.header().number())
-                ),
-                Err(err) => log::error!(
-                    target: "actor",
-                    "Failed to dispatch `BatchBlock` message into `{}`, height = [{:?}~{:?}]: {}",
-                    name,
-                    message.inner().first().map(|block| block.inner.block.header().number()),
-                    message.inner().last().map(|block| block.inner.block.header().number()),
-                    err
-                ),
-            }
+    /// Wake up the relays after the outbox records have been committed.
+    pub fn notify(&self) {
+        for (_, notify) in self.relays.values() {
+            notify.notify_one();
         }
-        Ok(())
     }
 
-    pub async fn dispatch_finalized_block(
-        &self,
-        message: FinalizedBlockMessage<Block>,
-    ) -> Result<(), Disconnected> {
-        let results = future::join_all(self.finalized_block.iter().map(|(name, dispatcher)| {
-            dispatcher
-                .send(message.clone())
+    pub async fn dispatch_flush(&self, message: Flush) -> Result<(), Disconnected> {
+        let results = future::join_all(self.relays.iter().map(|(name, (relay, _))| {
+            relay
+                .send(message)
                 .then(move |result| future::ready((name.clone(), result)))
         }))
         .await;
         for (name, result) in results {
             match result {
-                Ok(_) => log::debug!(
-                    target: "actor",
-                    "Dispatch `FinalizedBlock` message into `{}`, height = {}",
-                    name, message.block_num
-                ),
+                Ok(_) => log::debug!(target: "actor", "Dispatch `Flush` message into `{}`", name),
                 Err(err) => log::error!(
                     target: "actor",
-                    "Failed to dispatch `FinalizedBlock` message into `{}`, height = {}: {}",
-                    name, message.block_num, err
+                    "Failed to dispatch `Flush` message into `{}`: {}",
+                    name, err
                 ),
             }
         }
@@ -180,8 +108,8 @@ impl<Block: BlockT> Dispatcher<Block> {
     }
 
     pub async fn dispatch_die(&self, message: Die) -> Result<(), Disconnected> {
-        let results = future::join_all(self.die.iter().map(|(name, dispatcher)| {
-            dispatcher
+        let results = future::join_all(self.relays.iter().map(|(name, (relay, _))| {
+            relay
                 .send(message)
                 .then(move |result| future::ready((name.clone(), result)))
         }))
@@ -199,3 +127,62 @@ impl<Block: BlockT> Dispatcher<Block> {
         Ok(())
     }
 }
+
+pub fn outbox_model(record: PayloadRecord) -> OutboxModel {
+    OutboxModel {
+        kind: record.kind.to_string(),
+        key: record.key,
+        payload: record.payload,
+    }
+}
+
+fn payload_record(model: OutboxModel) -> Result<PayloadRecord, String> {
+    Ok(PayloadRecord {
+        kind: model.kind.parse()?,
+        key: model.key,
+        payload: model.payload,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use archive_kafka::PayloadKind;
+
+    #[test]
+    fn outbox_round_trip() {
+        for kind in [
+            PayloadKind::Metadata,
+            PayloadKind::Block,
+            PayloadKind::BestBlock,
+            PayloadKind::FinalizedBlock,
+            PayloadKind::Rollback,
+        ] {
+            let record = PayloadRecord {
+                kind,
+                key: Some("1".into()),
+                payload: r#"{"blockNum":1}"#.into(),
+            };
+            let model = outbox_model(record.clone());
+            assert_eq!(model.kind, kind.as_str());
+            assert_eq!(payload_record(model), Ok(record));
+        }
+
+        let record = PayloadRecord {
+            kind: PayloadKind::Rollback,
+            key: None,
+            payload: "{}".into(),
+        };
+        assert_eq!(payload_record(outbox_model(record.clone())), Ok(record));
+    }
+
+    #[test]
+    fn outbox_of_unknown_kind() {
+        let model = OutboxModel {
+            kind: "unknown".into(),
+            key: None,
+            payload: "{}".into(),
+        };
+        assert!(payload_record(model).is_err());
+    }
+}
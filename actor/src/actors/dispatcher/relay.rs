@@ -0,0 +1,301 @@
+use std::{cmp, future::Future, sync::Arc, time::Duration};
+
+use futures::{future, FutureExt};
+use tokio::sync::Notify;
+use xtra::prelude::*;
+
+use archive_kafka::PayloadRecord;
+use archive_postgres::{OutboxModel, PostgresDb};
+
+use super::{payload_record, DispatchActor};
+use crate::{
+    config::RelayConfig,
+    error::ActorError,
+    message::{Die, Flush, PublishMessage, Relay},
+};
+
+/// Publish the outbox to the sink in the order of id, starting from the committed offset of the sink.
+///
+/// The offset is committed after the records are published, so the records are published
+/// at least once, the records after a failed one are not published until it succeeds.
+pub struct RelayActor {
+    name: String,
+    db: PostgresDb,
+    config: RelayConfig,
+    notify: Arc<Notify>,
+    publish: Box<dyn StrongMessageChannel<PublishMessage>>,
+    flush: Box<dyn StrongMessageChannel<Flush>>,
+    die: Box<dyn StrongMessageChannel<Die>>,
+    // The id of the last published outbox record, `None` until it's loaded from db.
+    offset: Option<i64>,
+}
+
+impl RelayActor {
+    pub fn new<D: DispatchActor>(
+        name: String,
+        db: PostgresDb,
+        config: RelayConfig,
+        notify: Arc<Notify>,
+        sink: Address<D>,
+    ) -> Self {
+        Self {
+            name,
+            db,
+            config,
+            notify,
+            publish: Box::new(sink.clone()),
+            flush: Box::new(sink.clone()),
+            die: Box::new(sink),
+            offset: None,
+        }
+    }
+
+    async fn load_offset(&mut self) -> Result<i64, ActorError> {
+        if let Some(offset) = self.offset {
+            return Ok(offset);
+        }
+        // the sink has been registered when the dispatcher is created.
+        let offset = self.db.outbox_offset(&self.name).await?;
+        log::info!(target: "actor", "Relay `{}` resumes from outbox #{}", self.name, offset);
+        self.offset = Some(offset);
+        Ok(offset)
+    }
+
+    async fn commit_offset(&mut self, offset: i64) -> Result<(), ActorError> {
+        if self.offset != Some(offset) {
+            self.db.commit_outbox_offset(&self.name, offset).await?;
+            self.offset = Some(offset);
+        }
+        Ok(())
+    }
+
+    // Publish the records after the offset until the outbox is drained or the sink fails.
+    // Returns the number of published records.
+    async fn relay(&mut self) -> Result<u64, ActorError> {
+        let mut offset = self.load_offset().await?;
+        let mut published = 0;
+        loop {
+            let records = self
+                .db
+                .outbox_after(offset, i64::from(self.config.batch_size))
+                .await?;
+            let len = records.len();
+            let publish = &self.publish;
+            let (last, count, failed) =
+                publish_records(&self.name, offset, records, |id, record| {
+                    publish
+                        .send(PublishMessage { id, record })
+                        .map(|result| -> Result<(), ActorError> { result? })
+                })
+                .await;
+            offset = last;
+            published += count;
+            self.commit_offset(offset).await?;
+            if let Some(err) = failed {
+                // the failed record is published again by the next relay.
+                return Err(err);
+            }
+            if len < self.config.batch_size as usize {
+                break;
+            }
+        }
+        Ok(published)
+    }
+}
+
+// Publish the records in the order of id until one of them fails, the records that can't be
+// converted into payloads are skipped. Returns the id of the last published or skipped record
+// (`offset` if there is none), the number of published records and the error of the failed record.
+async fn publish_records<F, Fut>(
+    name: &str,
+    mut offset: i64,
+    records: Vec<(i64, OutboxModel)>,
+    mut publish: F,
+) -> (i64, u64, Option<ActorError>)
+where
+    F: FnMut(i64, PayloadRecord) -> Fut,
+    Fut: Future<Output = Result<(), ActorError>>,
+{
+    let mut published = 0;
+    for (id, model) in records {
+        match payload_record(model) {
+            Ok(record) => {
+                if let Err(err) = publish(id, record).await {
+                    return (offset, published, Some(err));
+                }
+                published += 1;
+            }
+            Err(err) => {
+                log::error!(target: "actor", "Relay `{}` skips outbox #{}: {}", name, id, err)
+            }
+        }
+        offset = id;
+    }
+    (offset, published, None)
+}
+
+// The backoff before retrying the failed sink, doubled after every failure up to `max`.
+fn next_backoff(backoff: Duration, max: Duration) -> Duration {
+    cmp::min(backoff * 2, max)
+}
+
+#[async_trait::async_trait]
+impl Actor for RelayActor {
+    async fn started(&mut self, ctx: &mut Context<Self>) {
+        let addr = ctx.address().expect("Actor just started");
+        let name = self.name.clone();
+        let notify = self.notify.clone();
+        let interval = Duration::from_millis(self.config.interval_ms);
+        let min_backoff = Duration::from_millis(self.config.backoff_ms);
+        let max_backoff = Duration::from_millis(self.config.max_backoff_ms);
+        tokio::task::spawn(async move {
+            let mut backoff = min_backoff;
+            loop {
+                match addr.send(Relay).await {
+                    Ok(Ok(_)) => {
+                        backoff = min_backoff;
+                        // wake up early when new records are committed.
+                        let sleep = Box::pin(tokio::time::sleep(interval));
+                        let notified = Box::pin(notify.notified());
+                        future::select(sleep, notified).await;
+                    }
+                    Ok(Err(err)) => {
+                        log::error!(
+                            target: "actor",
+                            "Relay `{}` failed, retry after {:?}: {}",
+                            name, backoff, err
+                        );
+                        tokio::time::sleep(backoff).await;
+                        backoff = next_backoff(backoff, max_backoff);
+                    }
+                    Err(_) => {
+                        log::error!(target: "actor", "Relay `{}` Actor Disconnected", name);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[async_trait::async_trait]
+impl Handler<Relay> for RelayActor {
+    async fn handle(&mut self, _: Relay, _: &mut Context<Self>) -> <Relay as Message>::Result {
+        let published = self.relay().await?;
+        if published > 0 {
+            log::debug!(target: "actor", "Relay {} outbox records into `{}`", published, self.name);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Handler<Flush> for RelayActor {
+    async fn handle(
+        &mut self,
+        message: Flush,
+        _: &mut Context<Self>,
+    ) -> <Flush as Message>::Result {
+        // publish the committed records before flushing the sink, the others are left in the outbox.
+        if let Err(err) = self.relay().await {
+            log::error!(target: "actor", "Relay `{}` failed when flushing: {}", self.name, err);
+        }
+        if let Err(err) = self.flush.send(message).await {
+            log::error!(target: "actor", "Flush `{}` But Disconnected: {}", self.name, err);
+        }
+        log::info!(target: "actor", "Flushed Relay Actor of `{}`", self.name);
+    }
+}
+
+#[async_trait::async_trait]
+impl Handler<Die> for RelayActor {
+    async fn handle(&mut self, message: Die, ctx: &mut Context<Self>) -> <Die as Message>::Result {
+        log::info!(target: "actor", "Stopping Relay Actor of `{}`", self.name);
+        if let Err(err) = self.die.send(message).await {
+            log::error!(target: "actor", "Stop `{}` But Disconnected: {}", self.name, err);
+        }
+        ctx.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outbox(kind: &str, key: u32) -> OutboxModel {
+        OutboxModel {
+            kind: kind.into(),
+            key: Some(key.to_string()),
+            payload: "{}".into(),
+        }
+    }
+
+    fn records() -> Vec<(i64, OutboxModel)> {
+        vec![
+            (11, outbox("block", 1)),
+            (12, outbox("unknown", 2)),
+            (13, outbox("block", 3)),
+            (14, outbox("block", 4)),
+        ]
+    }
+
+    #[test]
+    fn publish_all_records() {
+        let mut keys = Vec::new();
+        let (offset, published, failed) =
+            futures::executor::block_on(publish_records("test", 10, records(), |_, record| {
+                keys.push(record.key);
+                future::ready(Ok(()))
+            }));
+        assert_eq!((offset, published), (14, 3));
+        assert!(failed.is_none());
+        // the record of unknown kind is skipped.
+        assert_eq!(
+            keys,
+            vec![Some("1".into()), Some("3".into()), Some("4".into())]
+        );
+    }
+
+    #[test]
+    fn stop_at_failed_record() {
+        let (offset, published, failed) =
+            futures::executor::block_on(publish_records("test", 10, records(), |id, _| {
+                future::ready(match id {
+                    13 => Err(ActorError::MissingBlock(3)),
+                    _ => Ok(()),
+                })
+            }));
+        // the offset stops before the failed record, which is published again by the next relay.
+        assert_eq!((offset, published), (12, 1));
+        assert!(matches!(failed, Some(ActorError::MissingBlock(3))));
+
+        let (offset, published, failed) =
+            futures::executor::block_on(publish_records("test", 10, records(), |_, _| {
+                future::ready(Err(ActorError::MissingBlock(1)))
+            }));
+        assert_eq!((offset, published), (10, 0));
+        assert!(failed.is_some());
+    }
+
+    #[test]
+    fn publish_no_record() {
+        let (offset, published, failed) =
+            futures::executor::block_on(publish_records("test", 10, vec![], |_, _| {
+                future::ready(Ok(()))
+            }));
+        assert_eq!((offset, published), (10, 0));
+        assert!(failed.is_none());
+    }
+
+    #[test]
+    fn double_backoff_up_to_max() {
+        let max = Duration::from_millis(5000);
+        let mut backoff = Duration::from_millis(1000);
+        let mut backoffs = Vec::new();
+        for _ in 0..4 {
+            backoff = next_backoff(backoff, max);
+            backoffs.push(backoff.as_millis());
+        }
+        assert_eq!(backoffs, vec![2000, 4000, 5000, 5000]);
+    }
+}
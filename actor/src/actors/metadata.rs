@@ -10,7 +10,7 @@ use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
 use crate::{
     actors::postgres::PostgresActor,
     error::ActorError,
-    message::{BatchBlockMessage, BlockMessage, DbIfMetadataExist, Die, MetadataMessage},
+    message::{BatchBlockMessage, BlockMessage, DbIfMetadataExist, Die, Flush, MetadataMessage},
 };
 
 pub trait GetMetadata<Block: BlockT>: Send + Sync {
@@ -74,8 +74,7 @@ impl<Block: BlockT> MetadataActor<Block> {
             message.inner.block.hash(),
         )
         .await?;
-        self.db.send(message).await?;
-        Ok(())
+        self.db.send(message).await?
     }
 
     async fn batch_block_handler(
@@ -90,8 +89,7 @@ impl<Block: BlockT> MetadataActor<Block> {
             )
             .await?;
         }
-        self.db.send(message).await?;
-        Ok(())
+        self.db.send(message).await?
     }
 }
 
@@ -105,9 +103,7 @@ impl<Block: BlockT> Handler<BlockMessage<Block>> for MetadataActor<Block> {
         message: BlockMessage<Block>,
         _: &mut Context<Self>,
     ) -> <BlockMessage<Block> as Message>::Result {
-        if let Err(err) = self.block_handler(message).await {
-            log::error!(target: "actor", "{}", err);
-        }
+        self.block_handler(message).await
     }
 }
 
@@ -118,9 +114,14 @@ impl<Block: BlockT> Handler<BatchBlockMessage<Block>> for MetadataActor<Block> {
         message: BatchBlockMessage<Block>,
         _: &mut Context<Self>,
     ) -> <BatchBlockMessage<Block> as Message>::Result {
-        if let Err(err) = self.batch_block_handler(message).await {
-            log::error!(target: "actor", "{}", err);
-        }
+        self.batch_block_handler(message).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<Block: BlockT> Handler<Flush> for MetadataActor<Block> {
+    async fn handle(&mut self, _: Flush, _: &mut Context<Self>) -> <Flush as Message>::Result {
+        log::info!(target: "actor", "Flushed Metadata Actor");
     }
 }
 
@@ -3,7 +3,13 @@ mod metadata;
 mod postgres;
 mod scheduler;
 
-use std::sync::Arc;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use xtra::{prelude::*, spawn::TokioGlobalSpawnExt};
 
@@ -14,22 +20,28 @@ use sc_client_api::{
 use sp_api::{ApiExt, Core as CoreApi, Metadata as MetadataApi, ProvideRuntimeApi};
 use sp_runtime::traits::Block as BlockT;
 
+use archive_postgres::PostgresDb;
+
 use crate::{
-    config::{ActorConfig, DispatcherConfig},
+    config::{ActorConfig, DispatcherConfig, MailboxConfig},
     error::ActorError,
     message::*,
 };
 
-/// The direction of data flow:
-///                                                     ┌───────┐
-///     ┌───────────────────────────────┐         ┌────►│ kafka │
-///     │                               │         │     └───────┘
-/// ┌───┴───┐     ┌──────────┐     ┌────▼─────┐   │     ┌───────┐
-/// │ block ├────►│ metadata ├────►│ postgres ├───┼────►│  ...  │
-/// └───────┘     └──────────┘     └──────────┘   │     └───────┘
-///                                               │     ┌───────┐
-///                                               └────►│  ...  │
-///                                                     └───────┘
+// The name of the kafka sink, which identifies its offset of the outbox.
+const KAFKA_SINK: &str = "kafka";
+
+/// The direction of data flow, the payloads to the sinks are written into the outbox
+/// together with the data, and published by the relay of every sink:
+///                                                                  ┌───────┐     ┌───────┐
+///     ┌───────────────────────────────┐                      ┌────►│ relay ├────►│ kafka │
+///     │                               │                      │     └───────┘     └───────┘
+/// ┌───┴───┐     ┌──────────┐     ┌────▼─────┐     ┌────────┐  │     ┌───────┐     ┌───────┐
+/// │ block ├────►│ metadata ├────►│ postgres ├────►│ outbox ├──┼────►│ relay ├────►│  ...  │
+/// └───────┘     └──────────┘     └──────────┘     └────────┘  │     └───────┘     └───────┘
+///                                                             │     ┌───────┐     ┌───────┐
+///                                                             └────►│ relay ├────►│  ...  │
+///                                                                   └───────┘     └───────┘
 ///
 pub struct Actors<Block, Backend, Api>
 where
@@ -40,9 +52,16 @@ where
         + MetadataApi<Block>
         + ApiExt<Block, StateBackend = StateBackendFor<Backend, Block>>,
 {
+    postgres: PostgresDb,
     db: Address<postgres::PostgresActor<Block>>,
     metadata: Address<metadata::MetadataActor<Block>>,
     scheduler: Address<scheduler::Scheduler<Block, Backend, Api>>,
+    dispatcher: Option<dispatcher::Dispatcher>,
+    kafka: Option<Address<dispatcher::kafka::KafkaActor>>,
+    // Means if the tick loop of scheduler is running.
+    ticking: Arc<AtomicBool>,
+    // Means if the tick loop of scheduler should stop.
+    stopping: Arc<AtomicBool>,
 }
 
 impl<Block, Backend, Api> Actors<Block, Backend, Api>
@@ -59,15 +78,18 @@ where
         api: Arc<Api>,
         config: ActorConfig,
     ) -> Result<Self, ActorError> {
-        let dispatcher = Self::spawn_dispatcher(config.dispatcher)?;
-        let db = postgres::PostgresActor::<Block>::new(config.postgres, dispatcher)
+        let mailbox = config.mailbox;
+        let postgres = PostgresDb::new(config.postgres).await?;
+        let (dispatcher, kafka) =
+            Self::spawn_dispatcher(config.dispatcher, postgres.clone(), mailbox).await?;
+        let db = postgres::PostgresActor::<Block>::new(postgres.clone(), dispatcher.clone())
             .await?
-            .create(None)
+            .create(Some(mailbox.postgres))
             .spawn_global();
         log::info!(target: "actor", "Spawn Postgres Actor");
 
         let metadata = metadata::MetadataActor::<Block>::new(api.clone(), db.clone())
-            .create(None)
+            .create(Some(mailbox.metadata))
             .spawn_global();
         log::info!(target: "actor", "Spawn Metadata Actor");
 
@@ -78,43 +100,94 @@ where
             metadata.clone(),
             config.genesis,
             config.scheduler,
+            mailbox,
         )
-        .create(None)
+        .create(Some(mailbox.scheduler))
         .spawn_global();
         log::info!(target: "actor", "Spawn Scheduler Actor");
 
         Ok(Self {
+            postgres,
             db,
             metadata,
             scheduler,
+            dispatcher,
+            kafka,
+            ticking: Arc::new(AtomicBool::new(false)),
+            stopping: Arc::new(AtomicBool::new(false)),
         })
     }
 
-    fn spawn_dispatcher(
+    #[allow(clippy::type_complexity)]
+    async fn spawn_dispatcher(
         config: Option<DispatcherConfig>,
-    ) -> Result<Option<dispatcher::Dispatcher<Block>>, ActorError> {
+        db: PostgresDb,
+        mailbox: MailboxConfig,
+    ) -> Result<
+        (
+            Option<dispatcher::Dispatcher>,
+            Option<Address<dispatcher::kafka::KafkaActor>>,
+        ),
+        ActorError,
+    > {
         if let Some(config) = config {
-            let mut dispatcher = dispatcher::Dispatcher::<Block>::new();
+            let mut sinks = Vec::new();
+            if config.kafka.is_some() {
+                sinks.push(KAFKA_SINK);
+            }
+            let mut dispatcher = dispatcher::Dispatcher::new(db, config.relay, &sinks).await?;
+            let mut kafka = None;
             if let Some(config) = config.kafka {
-                let kafka = dispatcher::kafka::KafkaActor::<Block>::new(config)?
-                    .create(None)
+                let addr = dispatcher::kafka::KafkaActor::new(config)?
+                    .create(Some(mailbox.kafka))
                     .spawn_global();
                 log::info!(target: "actor", "Spawn Kafka Actor");
-                dispatcher.add("kafka", kafka);
+                dispatcher.add(KAFKA_SINK, addr.clone());
                 log::info!(target: "actor", "Add Kafka Actor into dispatcher");
+                kafka = Some(addr);
             }
-            Ok(Some(dispatcher))
+            Ok((Some(dispatcher), kafka))
         } else {
-            Ok(None)
+            Ok((None, None))
+        }
+    }
+
+    /// Returns the names of the actors that have stopped unexpectedly since `tick_interval`.
+    pub fn stopped_actors(&self) -> Vec<&'static str> {
+        let mut stopped = Vec::new();
+        if !self.scheduler.is_connected() {
+            stopped.push("scheduler");
+        } else if !self.ticking.load(Ordering::SeqCst) {
+            stopped.push("scheduler tick loop");
+        }
+        if !self.metadata.is_connected() {
+            stopped.push("metadata");
+        }
+        if !self.db.is_connected() {
+            stopped.push("postgres");
         }
+        if let Some(dispatcher) = &self.dispatcher {
+            if !dispatcher.is_connected() {
+                stopped.push("relay");
+            }
+        }
+        if let Some(kafka) = &self.kafka {
+            if !kafka.is_connected() {
+                stopped.push("kafka");
+            }
+        }
+        stopped
     }
 
     pub async fn tick_interval(&self) -> Result<(), ActorError> {
         // messages that only need to be sent once
         self.scheduler.send(Initialize).await?;
         let scheduler = self.scheduler.clone();
+        let ticking = self.ticking.clone();
+        let stopping = self.stopping.clone();
+        ticking.store(true, Ordering::SeqCst);
         tokio::task::spawn(async move {
-            loop {
+            while !stopping.load(Ordering::SeqCst) {
                 match scheduler.send(Tick).await {
                     Ok(Ok(_)) => {}
                     Ok(Err(err)) => {
@@ -127,17 +200,97 @@ where
                     }
                 }
             }
+            ticking.store(false, Ordering::SeqCst);
         });
         Ok(())
     }
 
-    pub async fn kill(self) -> Result<(), ActorError> {
-        self.scheduler.send(Die).await?;
-        log::info!(target: "actor", "Stopped Scheduler Actor");
-        self.metadata.send(Die).await?;
-        log::info!(target: "actor", "Stopped Metadata Actor");
-        self.db.send(Die).await?;
-        log::info!(target: "actor", "Stopped Postgres Actor");
+    /// Archive the blocks within the range `[from, to]`, returns when all of them are stored.
+    pub async fn backfill(&self, from: u32, to: u32) -> Result<(), ActorError> {
+        log::info!(target: "actor", "Start to backfill Block #{} ~ #{}", from, to);
+        let mut next = from;
+        while next <= to {
+            let last = self.scheduler.send(Backfill::new(next, to)).await??;
+            if last == u32::MAX {
+                break;
+            }
+            next = last + 1;
+        }
+        log::info!(target: "actor", "Finish backfilling Block #{} ~ #{}", from, to);
+        Ok(())
+    }
+
+    /// Re-crawl the missing blocks within the range `[from, to]`, returns when all of them are stored.
+    pub async fn repair(&self, from: Option<u32>, to: Option<u32>) -> Result<(), ActorError> {
+        log::info!(target: "actor", "Start to repair the missing blocks");
+        let repaired = self
+            .scheduler
+            .send(Repair::new(Some(from.unwrap_or_default()), to))
+            .await??;
+        log::info!(target: "actor", "Finish repairing {} missing blocks", repaired);
         Ok(())
     }
+
+    /// Stop scheduling new blocks, and wait until the in-flight blocks have been handled by
+    /// the metadata, postgres and dispatcher actors, or the `timeout` is elapsed.
+    pub async fn drain(&self, timeout: Duration) {
+        log::info!(target: "actor", "Draining the in-flight blocks");
+        self.stopping.store(true, Ordering::SeqCst);
+        // flush the mailboxes along the direction of data flow.
+        let flush = async {
+            let _ = self.scheduler.send(Flush).await;
+            let _ = self.metadata.send(Flush).await;
+            let _ = self.db.send(Flush).await;
+        };
+        match tokio::time::timeout(timeout, flush).await {
+            Ok(()) => log::info!(target: "actor", "Drained the in-flight blocks"),
+            Err(_) => log::warn!(
+                target: "actor",
+                "Drain timeout after {:?}, the in-flight blocks may be lost",
+                timeout
+            ),
+        }
+    }
+
+    /// Stop all the actors and close the connection pool, every actor that doesn't stop
+    /// within the `timeout` is given up.
+    pub async fn kill(self, timeout: Duration) -> Result<(), ActorError> {
+        // some of the actors may have stopped unexpectedly, stop the others anyway.
+        Self::die("Scheduler", &self.scheduler, timeout).await;
+        Self::die("Metadata", &self.metadata, timeout).await;
+        if !Self::die("Postgres", &self.db, timeout).await {
+            // the dispatcher is stopped by the postgres actor normally.
+            if let Some(dispatcher) = &self.dispatcher {
+                match tokio::time::timeout(timeout, dispatcher.dispatch_die(Die)).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(_)) => log::error!(target: "actor", "Stop Dispatcher But Disconnected"),
+                    Err(_) => {
+                        log::error!(target: "actor", "Stop Dispatcher Timeout after {:?}", timeout)
+                    }
+                }
+            }
+        }
+        // the restarted actors create a new pool.
+        self.postgres.pool().close().await;
+        log::info!(target: "actor", "Closed Postgres Pool");
+        Ok(())
+    }
+
+    // Returns true if the actor has stopped within the `timeout`.
+    async fn die<A: Handler<Die>>(name: &str, addr: &Address<A>, timeout: Duration) -> bool {
+        match tokio::time::timeout(timeout, addr.send(Die)).await {
+            Ok(Ok(())) => {
+                log::info!(target: "actor", "Stopped {} Actor", name);
+                true
+            }
+            Ok(Err(_)) => {
+                log::error!(target: "actor", "Stop {} Actor But Disconnected", name);
+                false
+            }
+            Err(_) => {
+                log::error!(target: "actor", "Stop {} Actor Timeout after {:?}", name, timeout);
+                false
+            }
+        }
+    }
 }
@@ -1,127 +1,546 @@
-use std::{collections::HashSet, mem, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    iter, mem,
+};
 
+use codec::Encode;
 use xtra::prelude::*;
 
-use sp_runtime::traits::Block as BlockT;
+use sp_runtime::{
+    traits::{Block as BlockT, Header as HeaderT},
+    SaturatedConversion,
+};
 
-use archive_postgres::{model::*, query, PostgresConfig, PostgresDb};
+use archive_kafka::{
+    BestBlockPayload, BlockPayload, FinalizedBlockPayload, MetadataPayload, PayloadRecord,
+    RollbackPayload,
+};
+use archive_postgres::{model::*, PostgresDb, PostgresTransaction};
 
 use crate::{
-    actors::dispatcher::Dispatcher,
+    actors::dispatcher::{outbox_model, Dispatcher},
+    decode::{system_events_key, Decoder, Extrinsic},
     error::ActorError,
     message::{
         BatchBlockMessage, BestBlockMessage, BlockMessage, CatchupFinalized, DbBestBlock,
-        DbDeleteGtBlockNum, DbFinalizedBlock, DbIfMetadataExist, DbMaxBlock, Die,
-        FinalizedBlockMessage, MetadataMessage,
+        DbBlockGaps, DbBlockHashes, DbBlocksWithoutMainStorage, DbEnsurePartitions,
+        DbFinalizedBlock, DbIfMetadataExist, DbMaxBlock, DbOrphanGtBlockNum, Die,
+        FailedBlockMessage, FinalizedBlockMessage, Flush, MetadataMessage, RollbackMessage,
     },
 };
 
 pub struct PostgresActor<Block: BlockT> {
     db: PostgresDb,
-    dispatcher: Option<Dispatcher<Block>>,
+    dispatcher: Option<Dispatcher>,
     // Means if the current block of scheduler catching up the finalized block.
     catchup_finalized: bool,
+    // The metadata that haven't been committed, they will be committed together with the
+    // following blocks which use them.
+    pending_metadata: Vec<MetadataMessage<Block>>,
+    // The best and finalized blocks of the node that haven't been archived yet, they are committed
+    // together with the blocks they refer to, so that they never point to a missing block.
+    pending_best: Option<BestBlockMessage<Block>>,
+    pending_finalized: Option<FinalizedBlockMessage<Block>>,
+    // The versions of metadata that have been committed, loaded at startup.
+    metadata_versions: HashSet<u32>,
+    // The decoders of the metadata versions, `None` if the metadata can't be decoded.
+    decoders: HashMap<u32, Option<Decoder>>,
+    // The metadata versions whose storage prefixes have been committed in this run.
+    prefix_versions: HashSet<u32>,
+    events_key: Vec<u8>,
 }
 
 impl<Block: BlockT> PostgresActor<Block> {
-    pub async fn new(
-        config: PostgresConfig,
-        dispatcher: Option<Dispatcher<Block>>,
-    ) -> Result<Self, ActorError> {
-        let db = PostgresDb::new(config).await?;
+    pub async fn new(db: PostgresDb, dispatcher: Option<Dispatcher>) -> Result<Self, ActorError> {
+        let metadata_versions = db.metadata_versions().await?.into_iter().collect();
         Ok(Self {
             db,
             dispatcher,
             catchup_finalized: false,
+            pending_metadata: Vec::new(),
+            pending_best: None,
+            pending_finalized: None,
+            metadata_versions,
+            decoders: HashMap::new(),
+            prefix_versions: HashSet::new(),
+            events_key: system_events_key(),
         })
     }
 
-    async fn metadata_handler(&self, metadata: MetadataMessage<Block>) -> Result<(), ActorError> {
-        self.db
-            .insert(MetadataModel::from(metadata.clone()))
-            .await?;
+    // Returns true if the metadata has been committed or is pending to be committed.
+    fn contains_metadata(&self, version: u32) -> bool {
+        self.metadata_versions.contains(&version) || self.is_metadata_pending(version)
+    }
+
+    // The metadata is always sent by the metadata actor before the blocks using it,
+    // so a block with unknown metadata version is rejected instead of waiting for it.
+    fn ensure_metadata(&self, blocks: &[BlockMessage<Block>]) -> Result<(), ActorError> {
+        match blocks
+            .iter()
+            .find(|block| !self.contains_metadata(block.version))
+        {
+            Some(block) => Err(ActorError::MissingMetadata(block.version)),
+            None => Ok(()),
+        }
+    }
+
+    fn is_metadata_pending(&self, version: u32) -> bool {
+        self.pending_metadata
+            .iter()
+            .any(|metadata| metadata.version == version)
+    }
+
+    fn metadata_handler(&mut self, metadata: MetadataMessage<Block>) {
+        self.pending_metadata
+            .retain(|pending| pending.version != metadata.version);
+        self.pending_metadata.push(metadata);
+    }
+
+    // Returns the decoder of the metadata version, `None` if the metadata can't be decoded.
+    async fn decoder(&mut self, version: u32) -> Result<Option<&Decoder>, ActorError> {
+        if !self.decoders.contains_key(&version) {
+            let metadata = match self
+                .pending_metadata
+                .iter()
+                .find(|metadata| metadata.version == version)
+            {
+                Some(metadata) => metadata.metadata.clone(),
+                None => self
+                    .db
+                    .metadata(version)
+                    .await?
+                    .ok_or(ActorError::MissingMetadata(version))?,
+            };
+            let decoder = match Decoder::new(&metadata) {
+                Ok(decoder) => Some(decoder),
+                Err(err) => {
+                    log::warn!(
+                        target: "actor",
+                        "Cannot decode the blocks of metadata version {}: {}",
+                        version, err
+                    );
+                    None
+                }
+            };
+            self.decoders.insert(version, decoder);
+        }
+        Ok(self.decoders.get(&version).and_then(Option::as_ref))
+    }
+
+    // Decode the `System.Events` of the blocks, the events that can't be decoded are skipped.
+    async fn event_models(
+        &mut self,
+        blocks: &[BlockMessage<Block>],
+    ) -> Result<Vec<EventModel>, ActorError> {
+        let mut models = Vec::new();
+        for block in blocks {
+            let data = match block
+                .main_changes
+                .iter()
+                .find(|(key, _)| *key == self.events_key)
+            {
+                Some((_, Some(data))) => data,
+                _ => continue,
+            };
+            let block_num: u32 = (*block.inner.block.header().number()).saturated_into();
+            let decoder = match self.decoder(block.version).await? {
+                Some(decoder) => decoder,
+                None => continue,
+            };
+            match decoder.decode_events(data) {
+                Ok(events) => {
+                    let block_hash = block.inner.block.header().hash().as_ref().to_vec();
+                    models.extend(
+                        events
+                            .into_iter()
+                            .map(|event| event.into_model(block_num, block_hash.clone())),
+                    );
+                }
+                Err(err) => log::warn!(
+                    target: "actor",
+                    "Failed to decode the events of Block #{}: {}",
+                    block_num, err
+                ),
+            }
+        }
+        Ok(models)
+    }
+
+    // Decode the extrinsics of the blocks, only the raw bytes are kept for the extrinsics
+    // that can't be decoded.
+    async fn extrinsic_models(
+        &mut self,
+        blocks: &[BlockMessage<Block>],
+    ) -> Result<Vec<ExtrinsicModel>, ActorError> {
+        let mut models = Vec::new();
+        for block in blocks {
+            let block_num: u32 = (*block.inner.block.header().number()).saturated_into();
+            let block_hash = block.inner.block.header().hash().as_ref().to_vec();
+            let decoder = self.decoder(block.version).await?;
+            for (index, extrinsic) in block.inner.block.extrinsics().iter().enumerate() {
+                let index = index as u32;
+                let encoded = extrinsic.encode();
+                let extrinsic =
+                    match decoder.map(|decoder| decoder.decode_extrinsic(index, &encoded)) {
+                        Some(Ok(extrinsic)) => extrinsic,
+                        Some(Err(err)) => {
+                            log::warn!(
+                                target: "actor",
+                                "Failed to decode the extrinsic #{} of Block #{}: {}",
+                                index, block_num, err
+                            );
+                            Extrinsic::undecoded(index, &encoded)
+                        }
+                        None => Extrinsic::undecoded(index, &encoded),
+                    };
+                models.push(extrinsic.into_model(block_num, block_hash.clone()));
+            }
+        }
+        Ok(models)
+    }
+
+    // Decode the arguments of the changed map keys of the blocks, the keys that can't be
+    // decoded are skipped.
+    async fn storage_key_models(
+        &mut self,
+        blocks: &[BlockMessage<Block>],
+    ) -> Result<Vec<StorageKeyModel>, ActorError> {
+        let mut models = HashMap::new();
+        for block in blocks {
+            let decoder = match self.decoder(block.version).await? {
+                Some(decoder) => decoder,
+                None => continue,
+            };
+            for (key, _) in &block.main_changes {
+                if models.contains_key(key) {
+                    continue;
+                }
+                match decoder.decode_storage_key(key) {
+                    Ok(Some(model)) => {
+                        models.insert(key.clone(), model);
+                    }
+                    Ok(None) => {}
+                    Err(err) => log::debug!(
+                        target: "actor",
+                        "Failed to decode the storage key 0x{}: {}",
+                        hex::encode(key), err
+                    ),
+                }
+            }
+        }
+        Ok(models.into_values().collect())
+    }
+
+    // Collect the runtime codes and versions of the blocks, the contiguous blocks of the same
+    // runtime code are merged into one range, and a new range starts whenever the code changes.
+    fn runtime_models(
+        blocks: &[BlockMessage<Block>],
+    ) -> (Vec<RuntimeCodeModel>, Vec<RuntimeVersionModel>) {
+        let mut codes = HashMap::new();
+        for block in blocks {
+            if let Some(code) = &block.code {
+                codes
+                    .entry(block.code_hash.clone())
+                    .or_insert_with(|| RuntimeCodeModel {
+                        code_hash: block.code_hash.clone(),
+                        code: code.clone(),
+                    });
+            }
+        }
+        let mut models = blocks
+            .iter()
+            .map(|block| block.runtime_version_model())
+            .collect::<Vec<_>>();
+        models.sort_by_key(|model| model.first_block);
+        let mut versions = Vec::<RuntimeVersionModel>::new();
+        for model in models {
+            match versions.last_mut() {
+                Some(version)
+                    if version.code_hash == model.code_hash
+                        && version.last_block + 1 == model.first_block =>
+                {
+                    version.last_block = model.last_block;
+                }
+                _ => versions.push(model),
+            }
+        }
+        (codes.into_values().collect(), versions)
+    }
+
+    // The outbox models of the payloads to the sinks, the payloads are not serialized
+    // if there is no sink.
+    fn outbox(&self, records: impl IntoIterator<Item = PayloadRecord>) -> Vec<OutboxModel> {
+        match &self.dispatcher {
+            Some(_) => records.into_iter().map(outbox_model).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Wake up the relays after the outbox has been committed.
+    fn notify_dispatcher(&self) {
         if let Some(dispatcher) = &self.dispatcher {
-            dispatcher.dispatch_metadata(metadata).await?;
+            dispatcher.notify();
         }
+    }
+
+    // Insert the pending metadata and their outbox within the transaction.
+    async fn insert_pending_metadata(
+        &self,
+        tx: &mut PostgresTransaction,
+    ) -> Result<(), ActorError> {
+        for metadata in &self.pending_metadata {
+            tx.insert(MetadataModel::from(metadata.clone())).await?;
+        }
+        let outbox = self.outbox(
+            self.pending_metadata
+                .iter()
+                .map(|metadata| MetadataPayload::<Block>::from(metadata.clone()).into()),
+        );
+        tx.insert(outbox).await?;
         Ok(())
     }
 
-    async fn block_handler(&self, message: BlockMessage<Block>) -> Result<(), ActorError> {
-        let mut conn = self.db.conn().await?;
-        while !query::check_if_metadata_exists(message.version, &mut conn).await? {
-            tokio::time::sleep(Duration::from_millis(20)).await;
+    // Returns the metadata versions of the blocks whose storage prefixes haven't been committed,
+    // and their prefixes, the prefixes are committed together with the first blocks using them.
+    //
+    // It must be called after the blocks are decoded, so that the decoders have been loaded.
+    fn pending_prefixes(
+        &self,
+        blocks: &[BlockMessage<Block>],
+    ) -> (Vec<u32>, Vec<StoragePrefixModel>) {
+        let mut versions = Vec::new();
+        let mut prefixes = Vec::new();
+        for block in blocks {
+            if self.prefix_versions.contains(&block.version) || versions.contains(&block.version) {
+                continue;
+            }
+            versions.push(block.version);
+            if let Some(Some(decoder)) = self.decoders.get(&block.version) {
+                prefixes.extend(decoder.storage_prefix_models());
+            }
         }
-        mem::drop(conn);
+        (versions, prefixes)
+    }
 
-        let (block, main_storage, _child_storage): (
+    // Register the pending metadata after they have been committed.
+    fn register_pending_metadata(&mut self) {
+        for metadata in mem::take(&mut self.pending_metadata) {
+            self.metadata_versions.insert(metadata.version);
+        }
+    }
+
+    async fn block_handler(&mut self, message: BlockMessage<Block>) -> Result<(), ActorError> {
+        self.ensure_metadata(std::slice::from_ref(&message))?;
+        let events = self.event_models(std::slice::from_ref(&message)).await?;
+        let extrinsics = self
+            .extrinsic_models(std::slice::from_ref(&message))
+            .await?;
+        let storage_keys = self
+            .storage_key_models(std::slice::from_ref(&message))
+            .await?;
+        let (prefix_versions, prefixes) = self.pending_prefixes(std::slice::from_ref(&message));
+        let (codes, runtimes) = Self::runtime_models(std::slice::from_ref(&message));
+
+        let (block, main_storage, child_storage): (
             BlockModel,
             Vec<MainStorageChangeModel>,
             Vec<ChildStorageChangeModel>,
         ) = message.clone().into();
-        self.db.insert(block).await?;
-        self.db.insert(main_storage).await?;
-        // TODO: insert child storage into database.
-        // self.db.insert(_child_storage).await?;
-        if let Some(dispatcher) = &self.dispatcher {
-            dispatcher.dispatch_block(message).await?;
-        }
-        Ok(())
-    }
+        let mut tx = self.db.begin().await?;
+        self.insert_pending_metadata(&mut tx).await?;
+        tx.insert(block).await?;
+        tx.insert(codes).await?;
+        tx.insert(runtimes).await?;
+        tx.insert(main_storage).await?;
+        tx.insert(child_storage).await?;
+        tx.insert(events).await?;
+        tx.insert(extrinsics).await?;
+        tx.insert(prefixes).await?;
+        tx.insert(storage_keys).await?;
+        let block_num: u32 = (*message.inner.block.header().number()).saturated_into();
+        tx.clear_failed_blocks(vec![block_num]).await?;
+        let block_hash = message.inner.block.header().hash();
+        let outbox = self.outbox(iter::once(BlockPayload::<Block>::from(message).into()));
+        tx.insert(outbox).await?;
+        let head = self
+            .insert_pending_head(&mut tx, std::slice::from_ref(&block_hash))
+            .await?;
+        tx.commit().await?;
 
-    // Returns true if all metadata versions are in database
-    // Otherwise, returns false if some metadata versions are missing.
-    fn db_contains_metadata(blocks: &[BlockMessage<Block>], all_versions: &HashSet<u32>) -> bool {
-        let versions: HashSet<u32> = blocks.iter().map(|block| block.version).collect();
-        versions.is_subset(all_versions)
+        self.register_pending_metadata();
+        self.prefix_versions.extend(prefix_versions);
+        self.register_pending_head(head);
+        self.notify_dispatcher();
+        Ok(())
     }
 
     async fn batch_block_handler(
-        &self,
+        &mut self,
         message: BatchBlockMessage<Block>,
     ) -> Result<(), ActorError> {
-        let mut conn = self.db.conn().await?;
-        let all_versions: HashSet<u32> = query::get_all_metadata_versions(&mut conn)
-            .await?
-            .into_iter()
-            .collect();
-        while !Self::db_contains_metadata(message.inner(), &all_versions) {
-            tokio::time::sleep(Duration::from_millis(20)).await;
-        }
-        mem::drop(conn);
+        self.ensure_metadata(message.inner())?;
+        let events = self.event_models(message.inner()).await?;
+        let extrinsics = self.extrinsic_models(message.inner()).await?;
+        let storage_keys = self.storage_key_models(message.inner()).await?;
+        let (prefix_versions, prefixes) = self.pending_prefixes(message.inner());
+        let (codes, runtimes) = Self::runtime_models(message.inner());
 
-        let (blocks, main_storages, _child_storages): (
+        let (blocks, main_storages, child_storages): (
             Vec<BlockModel>,
             Vec<MainStorageChangeModel>,
             Vec<ChildStorageChangeModel>,
         ) = message.clone().into();
-        self.db.insert(blocks).await?;
-        self.db.insert(main_storages).await?;
-        // TODO: insert child storage into database.
-        // self.db.insert(_child_storages).await?;
-        if let Some(dispatcher) = &self.dispatcher {
-            dispatcher.dispatch_batch_block(message).await?;
-        }
+        let mut tx = self.db.begin().await?;
+        self.insert_pending_metadata(&mut tx).await?;
+        tx.insert(blocks).await?;
+        tx.insert(codes).await?;
+        tx.insert(runtimes).await?;
+        tx.insert(main_storages).await?;
+        tx.insert(child_storages).await?;
+        tx.insert(events).await?;
+        tx.insert(extrinsics).await?;
+        tx.insert(prefixes).await?;
+        tx.insert(storage_keys).await?;
+        let block_nums = message
+            .inner()
+            .iter()
+            .map(|block| (*block.inner.block.header().number()).saturated_into())
+            .collect::<Vec<u32>>();
+        tx.clear_failed_blocks(block_nums).await?;
+        let block_hashes = message
+            .inner()
+            .iter()
+            .map(|block| block.inner.block.header().hash())
+            .collect::<Vec<_>>();
+        let outbox = self.outbox(
+            message
+                .into_inner()
+                .into_iter()
+                .map(|block| BlockPayload::<Block>::from(block).into()),
+        );
+        tx.insert(outbox).await?;
+        let head = self.insert_pending_head(&mut tx, &block_hashes).await?;
+        tx.commit().await?;
+
+        self.register_pending_metadata();
+        self.prefix_versions.extend(prefix_versions);
+        self.register_pending_head(head);
+        self.notify_dispatcher();
         Ok(())
     }
 
-    async fn best_block_handler(&self, message: BestBlockMessage<Block>) -> Result<(), ActorError> {
-        self.db
-            .insert(BestBlockModel::from(message.clone()))
-            .await?;
+    // Returns true if the block has been archived into the canonical chain.
+    async fn is_archived(
+        &self,
+        block_num: <Block::Header as HeaderT>::Number,
+        block_hash: &Block::Hash,
+    ) -> Result<bool, ActorError> {
+        let block_num = block_num.saturated_into::<u32>();
+        let hashes = self.db.block_hashes(block_num, block_num).await?;
+        Ok(hashes
+            .iter()
+            .any(|(_, hash)| hash.as_slice() == block_hash.as_ref()))
+    }
+
+    async fn insert_best_block(
+        &self,
+        tx: &mut PostgresTransaction,
+        message: BestBlockMessage<Block>,
+    ) -> Result<(), ActorError> {
+        tx.insert(BestBlockModel::from(message.clone())).await?;
+        if self.catchup_finalized {
+            let outbox = self.outbox(iter::once(BestBlockPayload::<Block>::from(message).into()));
+            tx.insert(outbox).await?;
+        }
         Ok(())
     }
 
-    async fn finalized_block_handler(
+    async fn insert_finalized_block(
         &self,
+        tx: &mut PostgresTransaction,
         message: FinalizedBlockMessage<Block>,
     ) -> Result<(), ActorError> {
-        self.db
-            .insert(FinalizedBlockModel::from(message.clone()))
+        tx.insert(FinalizedBlockModel::from(message.clone()))
             .await?;
         if self.catchup_finalized {
-            if let Some(dispatcher) = &self.dispatcher {
-                dispatcher.dispatch_finalized_block(message).await?;
+            let outbox = self.outbox(iter::once(
+                FinalizedBlockPayload::<Block>::from(message).into(),
+            ));
+            tx.insert(outbox).await?;
+        }
+        Ok(())
+    }
+
+    // Insert the pending best and finalized blocks that refer to one of the blocks within the
+    // transaction. Returns whether the best and the finalized block have been inserted.
+    async fn insert_pending_head(
+        &self,
+        tx: &mut PostgresTransaction,
+        block_hashes: &[Block::Hash],
+    ) -> Result<(bool, bool), ActorError> {
+        let best = match &self.pending_best {
+            Some(best) if block_hashes.contains(&best.block_hash) => {
+                self.insert_best_block(tx, best.clone()).await?;
+                true
+            }
+            _ => false,
+        };
+        let finalized = match &self.pending_finalized {
+            Some(finalized) if block_hashes.contains(&finalized.block_hash) => {
+                self.insert_finalized_block(tx, finalized.clone()).await?;
+                true
             }
+            _ => false,
+        };
+        Ok((best, finalized))
+    }
+
+    // Drop the pending best and finalized blocks after they have been committed.
+    fn register_pending_head(&mut self, (best, finalized): (bool, bool)) {
+        if best {
+            self.pending_best = None;
+        }
+        if finalized {
+            self.pending_finalized = None;
+        }
+    }
+
+    // The best block is committed right away if it has been archived, otherwise it's pending
+    // until the block is archived, e.g. it's never committed when following the finalized block.
+    async fn best_block_handler(
+        &mut self,
+        message: BestBlockMessage<Block>,
+    ) -> Result<(), ActorError> {
+        if self
+            .is_archived(message.block_num, &message.block_hash)
+            .await?
+        {
+            let mut tx = self.db.begin().await?;
+            self.insert_best_block(&mut tx, message).await?;
+            tx.commit().await?;
+            self.pending_best = None;
+            self.notify_dispatcher();
+        } else {
+            self.pending_best = Some(message);
+        }
+        Ok(())
+    }
+
+    async fn finalized_block_handler(
+        &mut self,
+        message: FinalizedBlockMessage<Block>,
+    ) -> Result<(), ActorError> {
+        if self
+            .is_archived(message.block_num, &message.block_hash)
+            .await?
+        {
+            let mut tx = self.db.begin().await?;
+            self.insert_finalized_block(&mut tx, message).await?;
+            tx.commit().await?;
+            self.pending_finalized = None;
+            self.notify_dispatcher();
+        } else {
+            self.pending_finalized = Some(message);
         }
         Ok(())
     }
@@ -137,9 +556,7 @@ impl<Block: BlockT> Handler<MetadataMessage<Block>> for PostgresActor<Block> {
         message: MetadataMessage<Block>,
         _ctx: &mut Context<Self>,
     ) -> <MetadataMessage<Block> as Message>::Result {
-        if let Err(err) = self.metadata_handler(message).await {
-            log::error!(target: "actor", "{}", err);
-        }
+        self.metadata_handler(message);
     }
 }
 
@@ -150,9 +567,7 @@ impl<Block: BlockT> Handler<BlockMessage<Block>> for PostgresActor<Block> {
         message: BlockMessage<Block>,
         _ctx: &mut Context<Self>,
     ) -> <BlockMessage<Block> as Message>::Result {
-        if let Err(err) = self.block_handler(message).await {
-            log::error!(target: "actor", "{}", err);
-        }
+        self.block_handler(message).await
     }
 }
 
@@ -163,9 +578,7 @@ impl<Block: BlockT> Handler<BatchBlockMessage<Block>> for PostgresActor<Block> {
         message: BatchBlockMessage<Block>,
         _ctx: &mut Context<Self>,
     ) -> <BatchBlockMessage<Block> as Message>::Result {
-        if let Err(err) = self.batch_block_handler(message).await {
-            log::error!(target: "actor", "{}", err);
-        }
+        self.batch_block_handler(message).await
     }
 }
 
@@ -176,9 +589,19 @@ impl<Block: BlockT> Handler<BestBlockMessage<Block>> for PostgresActor<Block> {
         message: BestBlockMessage<Block>,
         _ctx: &mut Context<Self>,
     ) -> <BestBlockMessage<Block> as Message>::Result {
-        if let Err(err) = self.best_block_handler(message).await {
-            log::error!(target: "actor", "{}", err);
-        }
+        self.best_block_handler(message).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<Block: BlockT> Handler<FailedBlockMessage> for PostgresActor<Block> {
+    async fn handle(
+        &mut self,
+        message: FailedBlockMessage,
+        _ctx: &mut Context<Self>,
+    ) -> <FailedBlockMessage as Message>::Result {
+        self.db.insert(FailedBlockModel::from(message)).await?;
+        Ok(())
     }
 }
 
@@ -189,9 +612,7 @@ impl<Block: BlockT> Handler<FinalizedBlockMessage<Block>> for PostgresActor<Bloc
         message: FinalizedBlockMessage<Block>,
         _ctx: &mut Context<Self>,
     ) -> <FinalizedBlockMessage<Block> as Message>::Result {
-        if let Err(err) = self.finalized_block_handler(message).await {
-            log::error!(target: "actor", "{}", err);
-        }
+        self.finalized_block_handler(message).await
     }
 }
 
@@ -202,7 +623,7 @@ impl<Block: BlockT> Handler<DbIfMetadataExist> for PostgresActor<Block> {
         message: DbIfMetadataExist,
         _ctx: &mut Context<Self>,
     ) -> <DbIfMetadataExist as Message>::Result {
-        self.db.if_metadata_exists(message.version).await
+        Ok(self.contains_metadata(message.version))
     }
 }
 
@@ -240,13 +661,89 @@ impl<Block: BlockT> Handler<DbFinalizedBlock> for PostgresActor<Block> {
 }
 
 #[async_trait::async_trait]
-impl<Block: BlockT> Handler<DbDeleteGtBlockNum> for PostgresActor<Block> {
+impl<Block: BlockT> Handler<DbOrphanGtBlockNum> for PostgresActor<Block> {
     async fn handle(
         &mut self,
-        message: DbDeleteGtBlockNum,
+        message: DbOrphanGtBlockNum,
         _: &mut Context<Self>,
-    ) -> <DbDeleteGtBlockNum as Message>::Result {
-        self.db.delete(message.block_num).await
+    ) -> <DbOrphanGtBlockNum as Message>::Result {
+        // the new tip is not orphaned, so it's looked up before the transaction.
+        let new_block_hash = match &self.dispatcher {
+            Some(_) => self
+                .db
+                .block_hashes(message.block_num, message.block_num)
+                .await?
+                .pop()
+                .map(|(_, hash)| hash),
+            None => None,
+        };
+
+        let mut tx = self.db.begin().await?;
+        let orphaned = tx.orphan_blocks(message.block_num).await?;
+        let rows = orphaned.len() as u64;
+        // tell the sinks to retract the blocks that they have received.
+        if self.dispatcher.is_some() {
+            if let Some(rollback) =
+                RollbackMessage::<Block>::new(message.block_num, new_block_hash, orphaned)?
+            {
+                let outbox =
+                    self.outbox(iter::once(RollbackPayload::<Block>::from(rollback).into()));
+                tx.insert(outbox).await?;
+            }
+        }
+        tx.commit().await?;
+
+        // the committed metadata is kept for the orphaned blocks, only the pending one is dropped.
+        self.pending_metadata
+            .retain(|metadata| metadata.block_num.saturated_into::<u32>() <= message.block_num);
+        self.notify_dispatcher();
+        Ok(rows)
+    }
+}
+
+#[async_trait::async_trait]
+impl<Block: BlockT> Handler<DbEnsurePartitions> for PostgresActor<Block> {
+    async fn handle(
+        &mut self,
+        message: DbEnsurePartitions,
+        _: &mut Context<Self>,
+    ) -> <DbEnsurePartitions as Message>::Result {
+        self.db.ensure_partitions(message.block_num).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<Block: BlockT> Handler<DbBlockHashes> for PostgresActor<Block> {
+    async fn handle(
+        &mut self,
+        message: DbBlockHashes,
+        _: &mut Context<Self>,
+    ) -> <DbBlockHashes as Message>::Result {
+        self.db.block_hashes(message.from, message.to).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<Block: BlockT> Handler<DbBlockGaps> for PostgresActor<Block> {
+    async fn handle(
+        &mut self,
+        message: DbBlockGaps,
+        _: &mut Context<Self>,
+    ) -> <DbBlockGaps as Message>::Result {
+        self.db.block_gaps(message.from, message.to).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<Block: BlockT> Handler<DbBlocksWithoutMainStorage> for PostgresActor<Block> {
+    async fn handle(
+        &mut self,
+        message: DbBlocksWithoutMainStorage,
+        _: &mut Context<Self>,
+    ) -> <DbBlocksWithoutMainStorage as Message>::Result {
+        self.db
+            .blocks_without_main_storage(message.from, message.to)
+            .await
     }
 }
 
@@ -262,6 +759,22 @@ impl<Block: BlockT> Handler<CatchupFinalized> for PostgresActor<Block> {
     }
 }
 
+#[async_trait::async_trait]
+impl<Block: BlockT> Handler<Flush> for PostgresActor<Block> {
+    async fn handle(
+        &mut self,
+        message: Flush,
+        _: &mut Context<Self>,
+    ) -> <Flush as Message>::Result {
+        if let Some(dispatcher) = &self.dispatcher {
+            if let Err(err) = dispatcher.dispatch_flush(message).await {
+                log::error!(target: "actor", "{}", err);
+            }
+        }
+        log::info!(target: "actor", "Flushed Postgres Actor");
+    }
+}
+
 #[async_trait::async_trait]
 impl<Block: BlockT> Handler<Die> for PostgresActor<Block> {
     async fn handle(&mut self, message: Die, ctx: &mut Context<Self>) -> <Die as Message>::Result {
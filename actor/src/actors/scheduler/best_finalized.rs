@@ -83,7 +83,7 @@ where
                     block_num: best_num,
                     block_hash: best_hash,
                 })
-                .await?;
+                .await??;
             self.best_block_num = best_block_num;
             self.best_block_hash = best_block_hash.to_vec();
         }
@@ -101,7 +101,7 @@ where
                     block_hash: finalized_hash,
                     timestamp: Utc::now().timestamp_millis(),
                 })
-                .await?;
+                .await??;
             self.finalized_block_num = finalized_block_num;
             self.finalized_block_hash = finalized_block_hash.to_vec();
         }
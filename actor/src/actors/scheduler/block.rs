@@ -7,19 +7,45 @@ use sc_client_api::{
     client::BlockBackend,
 };
 use sp_api::{ApiExt, BlockId, Core as CoreApi, ProvideRuntimeApi};
-use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
+use sp_blockchain::HeaderBackend;
+use sp_core::hashing::blake2_256;
+use sp_runtime::{
+    traits::{Block as BlockT, Header as HeaderT},
+    DigestItem, SaturatedConversion,
+};
+use sp_state_machine::{Backend as StateBackend, StorageCollection};
+use sp_storage::well_known_keys;
+use sp_version::RuntimeVersion;
 
 use crate::{
-    error::ActorError,
-    exec::BlockExecutor,
-    message::{BlockMessage, CrawlBlock, Die},
+    config::SyncMode,
+    error::{ActorError, BlockchainError},
+    exec::{BlockExecutor, StorageChanges},
+    message::{changed_code, BlockMessage, CrawlBlock, Die},
 };
 
+// The maximum number of headers that are walked back to reuse the runtime in the fast mode.
+const MAX_RUNTIME_WALK: u32 = 4096;
+
+// Returns true if the runtime (`:code` or heap pages) is updated by the block.
+fn is_runtime_updated<Header: HeaderT>(header: &Header) -> bool {
+    header
+        .digest()
+        .logs()
+        .iter()
+        .any(|log| matches!(log, DigestItem::RuntimeEnvironmentUpdated))
+}
+
 pub struct BlockActor<Block: BlockT, Backend, Api> {
     _marker: PhantomData<Block>,
     backend: Arc<Backend>,
     api: Arc<Api>,
+    mode: SyncMode,
     curr_block: u32,
+    // The runtime version and the hash of `:code` of the last crawled block.
+    last_runtime: Option<(RuntimeVersion, Vec<u8>)>,
+    // The number and hash of the last crawled block in the fast mode.
+    last_block: Option<(u32, Block::Hash)>,
 }
 
 impl<Block, Backend, Api> BlockActor<Block, Backend, Api>
@@ -30,15 +56,77 @@ where
     <Api as ProvideRuntimeApi<Block>>::Api:
         CoreApi<Block> + ApiExt<Block, StateBackend = StateBackendFor<Backend, Block>>,
 {
-    pub fn new(backend: Arc<Backend>, api: Arc<Api>) -> Self {
+    pub fn new(backend: Arc<Backend>, api: Arc<Api>, mode: SyncMode) -> Self {
         Self {
             _marker: PhantomData,
             backend,
             api,
+            mode,
             curr_block: 0,
+            last_runtime: None,
+            last_block: None,
+        }
+    }
+
+    // Returns the runtime of the last crawled block if the block descends from it and the runtime
+    // is not updated in between, only the headers are walked without touching the state.
+    fn cached_runtime(
+        &self,
+        header: &Block::Header,
+    ) -> Result<Option<(RuntimeVersion, Vec<u8>)>, ActorError> {
+        let (runtime, (last_num, last_hash)) = match (&self.last_runtime, &self.last_block) {
+            (Some(runtime), Some(last_block)) => (runtime, last_block),
+            _ => return Ok(None),
+        };
+        let block_num = (*header.number()).saturated_into::<u32>();
+        if block_num <= *last_num || block_num - last_num > MAX_RUNTIME_WALK {
+            return Ok(None);
+        }
+        let mut header = header.clone();
+        loop {
+            if is_runtime_updated(&header) {
+                return Ok(None);
+            }
+            let parent_hash = *header.parent_hash();
+            if (*header.number()).saturated_into::<u32>() == last_num + 1 {
+                return Ok((parent_hash == *last_hash).then(|| runtime.clone()));
+            }
+            header = match self
+                .backend
+                .blockchain()
+                .header(BlockId::Hash(parent_hash))?
+            {
+                Some(header) => header,
+                None => return Ok(None),
+            };
         }
     }
 
+    // Returns the hash of `:code` at the block, and the code if it's changed by the block
+    // or the runtime is different from the last crawled block.
+    fn code(
+        &mut self,
+        id: &BlockId<Block>,
+        runtime: &RuntimeVersion,
+        main_changes: &StorageCollection,
+    ) -> Result<(Vec<u8>, Option<Vec<u8>>), ActorError> {
+        let code = match (changed_code(main_changes), &self.last_runtime) {
+            (Some(code), _) => code.to_vec(),
+            (None, Some((last, code_hash))) if last == runtime => {
+                return Ok((code_hash.clone(), None))
+            }
+            (None, _) => self
+                .backend
+                .state_at(*id)?
+                .storage(well_known_keys::CODE)
+                .map_err(|err| BlockchainError::Storage(err.to_string()))?
+                .ok_or(ActorError::MissingCode(self.curr_block))?,
+        };
+        let code_hash = blake2_256(&code).to_vec();
+        self.last_runtime = Some((runtime.clone(), code_hash.clone()));
+        Ok((code_hash, Some(code)))
+    }
+
     async fn crawl(
         &mut self,
         message: CrawlBlock<Block>,
@@ -53,34 +141,62 @@ where
                 block.block.header().hash()
             );
 
-            if let Ok(runtime_version) = self.api.runtime_api().version(&id) {
-                log::debug!(
-                    target: "actor",
-                    "Executing Block #{} ({}), version {}",
-                    block.block.header().number(),
-                    block.block.header().hash(),
-                    runtime_version.spec_version
-                );
-
-                let now = Instant::now();
-                let executor =
-                    BlockExecutor::new(block.block.clone(), &self.backend, self.api.runtime_api());
-                let changes = executor.into_storage_changes()?;
-                log::debug!(
-                    target: "actor",
-                    "Took {:?} to execute block #{}",
-                    now.elapsed(), block.block.header().number()
-                );
-
-                Ok(Some(BlockMessage {
+            if self.mode == SyncMode::Fast {
+                let header = block.block.header();
+                let (runtime_version, code_hash, code) = match self.cached_runtime(header)? {
+                    Some((runtime_version, code_hash)) => (runtime_version, code_hash, None),
+                    None => {
+                        // the runtime is updated or unknown, only then it's read from the state.
+                        let runtime_version = self.api.runtime_api().version(&id)?;
+                        let (code_hash, code) =
+                            self.code(&id, &runtime_version, &Default::default())?;
+                        (runtime_version, code_hash, code)
+                    }
+                };
+                self.last_block = Some((self.curr_block, header.hash()));
+                return Ok(Some(BlockMessage {
                     version: runtime_version.spec_version,
                     inner: block,
-                    main_changes: changes.main_storage_changes,
-                    child_changes: changes.child_storage_changes,
-                }))
-            } else {
-                Ok(None)
+                    main_changes: Default::default(),
+                    child_changes: Default::default(),
+                    runtime: runtime_version,
+                    code_hash,
+                    code,
+                }));
             }
+
+            let runtime_version = self.api.runtime_api().version(&id)?;
+            log::debug!(
+                target: "actor",
+                "Executing Block #{} ({}), version {}",
+                block.block.header().number(),
+                block.block.header().hash(),
+                runtime_version.spec_version
+            );
+
+            let now = Instant::now();
+            let executor =
+                BlockExecutor::new(block.block.clone(), &self.backend, self.api.runtime_api());
+            let StorageChanges {
+                main_storage_changes,
+                child_storage_changes,
+            } = executor.into_storage_changes()?;
+            log::debug!(
+                target: "actor",
+                "Took {:?} to execute block #{}",
+                now.elapsed(), block.block.header().number()
+            );
+
+            let (code_hash, code) = self.code(&id, &runtime_version, &main_storage_changes)?;
+            Ok(Some(BlockMessage {
+                version: runtime_version.spec_version,
+                inner: block,
+                main_changes: main_storage_changes,
+                child_changes: child_storage_changes,
+                runtime: runtime_version,
+                code_hash,
+                code,
+            }))
         } else {
             Ok(None)
         }
@@ -110,14 +226,12 @@ where
         message: CrawlBlock<Block>,
         _: &mut Context<Self>,
     ) -> <CrawlBlock<Block> as Message>::Result {
-        match self.crawl(message).await {
-            Ok(block) => block,
-            // If error occurred, don't stop the actor.
-            Err(err) => {
-                log::error!(target: "actor", "{}", err);
-                None
-            }
-        }
+        let block_num = message.block_num();
+        // If error occurred, don't stop the actor, the scheduler decides whether to retry.
+        self.crawl(message).await.map_err(|err| {
+            log::error!(target: "actor", "Crawl Block #{}: {}", block_num, err);
+            err
+        })
     }
 }
 
@@ -1,8 +1,20 @@
 mod best_finalized;
 mod block;
+mod tuner;
+mod window;
 
-use std::{cmp, collections::BTreeMap, sync::Arc, time::Duration};
+use std::{
+    cmp,
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use futures::{
+    future::{self, BoxFuture, FutureExt},
+    stream::{FuturesUnordered, StreamExt},
+};
+use tokio::task::JoinHandle;
 use xtra::{prelude::*, spawn::TokioGlobalSpawnExt};
 
 use sc_client_api::{
@@ -11,20 +23,57 @@ use sc_client_api::{
 };
 use sp_api::{ApiExt, BlockId, Core as CoreApi, ProvideRuntimeApi};
 use sp_blockchain::HeaderBackend;
+use sp_core::hashing::blake2_256;
 use sp_runtime::{
     generic::SignedBlock,
     traits::{Block as BlockT, Header as HeaderT, NumberFor},
 };
 use sp_storage::Storage;
 
-use self::{best_finalized::BestAndFinalizedActor, block::BlockActor};
+use self::{
+    best_finalized::BestAndFinalizedActor, block::BlockActor, tuner::Tuner, window::ReorderBuffer,
+};
 use crate::{
     actors::{metadata::MetadataActor, postgres::PostgresActor},
-    config::SchedulerConfig,
+    config::{FollowMode, MailboxConfig, RetryConfig, SchedulerConfig, SyncMode},
     error::ActorError,
     message::*,
 };
 
+// The number of blocks that are fetched from db at a time when checking the consistency.
+const CONSISTENCY_CHECK_WINDOW: u32 = 1000;
+
+// The default memory bound of the crawled blocks waiting in the catch-up window.
+const DEFAULT_WINDOW_MEMORY_MB: usize = 512;
+
+// The backoff of re-crawling a failed block in the window stops doubling after these rounds.
+const MAX_RETRY_ROUNDS_DOUBLING: u32 = 6;
+
+// The pending reply of the block actor whose crawling timed out, the actor is busy executing
+// the block until the reply arrives, since the execution can't be cancelled.
+type StaleReply = BoxFuture<'static, ()>;
+
+// (the index of block actor, block number, elapsed time of crawling, crawled block,
+// stale reply of the timed-out crawling)
+type CrawlResult<Block> = (
+    usize,
+    u32,
+    Duration,
+    Result<(Option<BlockMessage<Block>>, Option<StaleReply>), ActorError>,
+);
+
+// Returns the highest block that can be archived after catching up with the finalized block,
+// `None` means following the best block of the node without limit.
+fn follow_head(follow: FollowMode, best_num: u32, finalized_num: u32) -> Option<u32> {
+    match follow {
+        FollowMode::Finalized => Some(finalized_num),
+        FollowMode::Best => None,
+        FollowMode::BestWithDepth(depth) => {
+            Some(cmp::max(best_num.saturating_sub(depth), finalized_num))
+        }
+    }
+}
+
 pub struct Scheduler<Block, Backend, Api>
 where
     Block: BlockT,
@@ -44,6 +93,10 @@ where
     config: SchedulerConfig,
     curr_block: u32,
     catchup_finalized: bool,
+    // the blocks before it have been checked by the repair.
+    unchecked_block: u32,
+    // Means if the scheduler stops scheduling new blocks for shutdown.
+    draining: bool,
 
     // curr_finalized_block                   curr_block
     //    |                                       |
@@ -54,6 +107,28 @@ where
     //    +---------------------------------------+
     //                    queue
     queue: BTreeMap<u32, Block::Header>,
+
+    // The catch-up window, the idle block actors keep crawling the blocks ahead,
+    // and the crawled blocks are emitted in order as contiguous batches.
+    //
+    // curr_block         reorder (crawled)         next_crawl
+    //    |   +-----+-----+     +-----+-----+          |
+    //    |◄--+ ... | ... | ... | ... | ... |◄-- ... --+ ... finalized_block
+    //        +-----+-----+     +-----+-----+
+    //             in_flight (crawling)
+    in_flight: FuturesUnordered<JoinHandle<CrawlResult<Block>>>,
+    idle_blocks: Vec<usize>,
+    // The block actors whose crawling timed out, they are kept out of `idle_blocks`
+    // until the stale replies arrive.
+    busy_blocks: BTreeMap<usize, StaleReply>,
+    reorder: ReorderBuffer<BlockMessage<Block>>,
+    next_crawl: u32,
+    // The failed blocks are crawled again after the backoff, the window is held at them.
+    retry_at: BTreeMap<u32, Instant>,
+    // The number of rounds that the blocks have failed in the window.
+    retry_rounds: HashMap<u32, u32>,
+    // tunes the number of crawling blocks and the batch size of the window.
+    tuner: Tuner,
 }
 
 impl<Block, Backend, Api> Scheduler<Block, Backend, Api>
@@ -71,26 +146,33 @@ where
         metadata: Address<MetadataActor<Block>>,
         genesis: Storage,
         config: SchedulerConfig,
+        mailbox: MailboxConfig,
     ) -> Self {
         let best_and_finalized = BestAndFinalizedActor::<Block, Backend>::new(
             backend.clone(),
             db.clone(),
             config.interval_ms,
         )
-        .create(None)
+        .create(Some(mailbox.best_and_finalized))
         .spawn_global();
         log::info!(target: "actor", "Spawn BestAndFinalized Actor");
 
-        let mut blocks = Vec::with_capacity(config.max_block_load as usize);
         assert!(config.max_block_load >= 1, "max_block_load must be >= 1");
-        for i in 0..config.max_block_load {
+        let tuner = Tuner::new(config.adaptive, config.max_block_load);
+        // spawn enough block actors for the upper bound of the in-flight blocks.
+        let num_blocks = config.adaptive.map_or(config.max_block_load, |adaptive| {
+            cmp::max(config.max_block_load, adaptive.max_in_flight)
+        });
+        let mut blocks = Vec::with_capacity(num_blocks as usize);
+        for i in 0..num_blocks {
             blocks.push(
-                BlockActor::<Block, Backend, Api>::new(backend.clone(), api.clone())
-                    .create(None)
+                BlockActor::<Block, Backend, Api>::new(backend.clone(), api.clone(), config.mode)
+                    .create(Some(mailbox.block))
                     .spawn_global(),
             );
             log::info!(target: "actor", "Spawn Block[{}] Actor", i);
         }
+        let idle_blocks = (0..blocks.len()).collect();
         let curr_block = config.start_block.unwrap_or_default();
 
         Self {
@@ -107,8 +189,19 @@ where
             config,
             curr_block,
             catchup_finalized: false,
+            unchecked_block: curr_block,
+            draining: false,
 
             queue: Default::default(),
+
+            in_flight: FuturesUnordered::new(),
+            idle_blocks,
+            busy_blocks: BTreeMap::new(),
+            reorder: ReorderBuffer::new(curr_block + 1),
+            next_crawl: curr_block + 1,
+            retry_at: BTreeMap::new(),
+            retry_rounds: HashMap::new(),
+            tuner,
         }
     }
 
@@ -126,21 +219,25 @@ where
     // +-----+
     async fn initialize(&mut self) -> Result<(), ActorError> {
         let (_best_block, finalized_block) = self.best_and_finalized().await?;
+        self.db
+            .send(DbEnsurePartitions::new(finalized_block))
+            .await??;
         if let Some(max) = self.db.send(DbMaxBlock).await?? {
+            let max = self.check_consistency(max).await?;
             if let Some(start_block) = self.config.start_block {
                 self.curr_block = cmp::min(finalized_block, start_block);
             } else {
                 self.curr_block = cmp::min(max, finalized_block);
             }
-            // delete the metadatas, blocks and storages (block_num > curr_block) from db
+            // orphan the blocks (block_num > curr_block) in db, they are kept as non-canonical forks
             let _ = self
                 .db
-                .send(DbDeleteGtBlockNum::new(self.curr_block))
+                .send(DbOrphanGtBlockNum::new(self.curr_block))
                 .await??;
         } else {
             // `None` means that the blocks table is empty yet
             let genesis_block = self.genesis_block()?;
-            self.metadata.send(genesis_block).await?;
+            self.metadata.send(genesis_block).await??;
         }
 
         // initialized block must exist
@@ -156,6 +253,44 @@ where
         Ok(())
     }
 
+    // Walk back from the `tip` of db to find the highest block whose hash matches the canonical chain
+    // of the node, and rollback to it. Returns the number of the highest common block.
+    async fn check_consistency(&mut self, tip: u32) -> Result<u32, ActorError> {
+        let mut invalidated = 0;
+        let mut to = tip;
+        let common = 'walk: loop {
+            let from = to.saturating_sub(CONSISTENCY_CHECK_WINDOW - 1);
+            let blocks = self.db.send(DbBlockHashes::new(from, to)).await??;
+            for (block_num, block_hash) in blocks {
+                let canonical_hash = self
+                    .backend
+                    .blockchain()
+                    .hash(<Block::Header as HeaderT>::Number::from(block_num))?;
+                match canonical_hash {
+                    Some(hash) if hash.as_ref() == block_hash.as_slice() => break 'walk block_num,
+                    _ => invalidated += 1,
+                }
+            }
+            if from == 0 {
+                // even the genesis block doesn't match, the db belongs to another chain.
+                return Err(ActorError::InconsistentBlock(0));
+            }
+            to = from - 1;
+        };
+
+        if invalidated > 0 {
+            log::warn!(
+                target: "actor",
+                "⚠️  {} blocks are inconsistent with the canonical chain, rollback to Block #{}",
+                invalidated, common
+            );
+            self.db.send(DbOrphanGtBlockNum::new(common)).await??;
+        } else {
+            log::info!(target: "actor", "Blocks are consistent with the canonical chain until Block #{}", common);
+        }
+        Ok(common)
+    }
+
     async fn best_and_finalized(&mut self) -> Result<(u32, u32), ActorError> {
         Ok(self.best_and_finalized.send(BestAndFinalized).await?)
     }
@@ -168,10 +303,17 @@ where
         let runtime_version = self.api.runtime_api().version(&id)?;
         let block = self.block(&id)?.expect("genesis block must exist; qed");
         let genesis = self.genesis.clone();
+        let main_changes: Vec<_> = genesis.top.into_iter().map(|(k, v)| (k, Some(v))).collect();
+        let code = changed_code(&main_changes)
+            .ok_or(ActorError::MissingCode(0))?
+            .to_vec();
         Ok(BlockMessage {
             version: runtime_version.spec_version,
             inner: block,
-            main_changes: genesis.top.into_iter().map(|(k, v)| (k, Some(v))).collect(),
+            runtime: runtime_version,
+            code_hash: blake2_256(&code).to_vec(),
+            code: Some(code),
+            main_changes,
             child_changes: genesis
                 .children_default
                 .into_iter()
@@ -222,7 +364,8 @@ where
 
     // main logic
     async fn tick(&mut self) -> Result<(), ActorError> {
-        let (_best_num, finalized_num) = self.best_and_finalized().await?;
+        let (best_num, finalized_num) = self.best_and_finalized().await?;
+        let head = follow_head(self.config.follow, best_num, finalized_num);
 
         // update the current finalized block
         if self.curr_finalized_block_num() != finalized_num {
@@ -244,21 +387,22 @@ where
                     "♻️  Rollback to Finalized Block #{}, Queue: {}",
                     finalized_num, self.log_queue(),
                 );
-                // delete forked block from db
+                // orphan forked blocks in db
                 self.db
-                    .send(DbDeleteGtBlockNum::new(finalized_num - 1))
+                    .send(DbOrphanGtBlockNum::new(finalized_num - 1))
                     .await??;
-                // re-construct queue
+                // re-construct queue, the failed finalized block stops the scheduler.
                 let finalized_block = self
                     .crawl_block(finalized_num)
                     .await?
-                    .expect("finalized block must exist");
+                    .ok_or(ActorError::MissingBlock(finalized_num))?;
                 let finalized_header = finalized_block.inner.block.header();
                 self.queue.clear();
                 self.queue.insert(finalized_num, finalized_header.clone());
-                // reset the curr_block
+                // reset the curr_block and the catch-up window
                 self.curr_block = finalized_num;
-                self.metadata.send(finalized_block).await?;
+                self.reset_window();
+                self.metadata.send(finalized_block).await??;
             }
             // remove the finalized blocks (remain one finalized block on the front) from the queue.
             self.queue.retain(|h, _| *h >= finalized_num);
@@ -269,21 +413,34 @@ where
             );
         }
 
-        if self.curr_block + self.config.max_block_load <= finalized_num {
+        // create the partitions ahead of the blocks that will be dispatched.
+        self.db
+            .send(DbEnsurePartitions::new(
+                self.curr_block + self.config.max_block_load,
+            ))
+            .await??;
+
+        if self.is_window_active() || self.curr_block + self.config.max_block_load <= finalized_num
+        {
             // Haven't caught up with the latest finalized block
-            self.tick_batch().await?;
+            self.tick_batch(finalized_num).await?;
         } else {
             if self.curr_block < finalized_num {
                 // It's about to catch up with the latest finalized block
                 self.tick_one_about_to_catch_up().await?;
             } else {
                 // Has caught up with the latest finalized block
-                self.tick_one_has_caught_up().await?;
+                self.tick_one_has_caught_up(head).await?;
             }
         }
 
-        // start to dispatch finalized block if we have dispatchers.
-        if !self.catchup_finalized && self.curr_block > finalized_num {
+        // start to dispatch finalized block if we have dispatchers,
+        // the finalized block is the head in the finalized mode.
+        let caught_up = match head {
+            Some(head) if head <= finalized_num => self.curr_block >= finalized_num,
+            _ => self.curr_block > finalized_num,
+        };
+        if !self.catchup_finalized && caught_up {
             log::info!(
                 target: "actor", "Scheduler catchup the finalized block (curr #{}, finalized #{})",
                 self.curr_block, finalized_num
@@ -303,27 +460,204 @@ where
     // |     |◄---+     |◄---+     |◄---...◄---+     |    queue: [curr_finalized_block]
     // +--+--+    +--+--+    +-----+           +--+--+
     //    |-------- >= max_block_load ------------|
-    async fn tick_batch(&mut self) -> Result<(), ActorError> {
-        // example:
-        // curr_block = 0; max_block_load = 10  => (0+1..0+10] => (1..10]
-        // curr_block = 10; max_block_load = 10 => (10+1..10+10] => (11..20]
-        let fut = ((self.curr_block + 1)..=(self.curr_block + self.config.max_block_load))
-            .map(|i| {
-                let index = (i % self.config.max_block_load) as usize;
-                log::debug!(target: "actor", "BlockActor[{}] Crawling Block #{}", index, i);
-                self.blocks[index].send(CrawlBlock::new(i))
-            })
-            .collect::<Vec<_>>();
-        let results = futures::future::join_all(fut).await;
-        let results = results
-            .into_iter()
-            .map(|result| result.unwrap().unwrap()) // shouldn't be none or error
+    //
+    // The blocks are crawled in the catch-up window, a slow block doesn't stall the idle block
+    // actors, they keep crawling ahead until the crawled blocks exceed the memory bound.
+    async fn tick_batch(&mut self, finalized_num: u32) -> Result<(), ActorError> {
+        if !self.is_window_active() {
+            self.reset_window();
+        }
+
+        // keep the idle block actors busy.
+        let window_bytes = self
+            .config
+            .window_memory_mb
+            .unwrap_or(DEFAULT_WINDOW_MEMORY_MB)
+            * 1024
+            * 1024;
+        // the failed blocks that are due are crawled again before the new blocks.
+        let now = Instant::now();
+        let due = self
+            .retry_at
+            .iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(num, _)| *num)
             .collect::<Vec<_>>();
-        self.metadata.send(BatchBlockMessage::new(results)).await?;
-        self.curr_block += self.config.max_block_load;
+        for num in due {
+            if self.in_flight.len() >= self.tuner.in_flight() || !self.spawn_crawl(num) {
+                break;
+            }
+            self.retry_at.remove(&num);
+        }
+        while self.next_crawl <= finalized_num
+            && self.reorder.bytes() < window_bytes
+            && self.in_flight.len() < self.tuner.in_flight()
+        {
+            if !self.spawn_crawl(self.next_crawl) {
+                break;
+            }
+            self.next_crawl += 1;
+        }
+
+        // wait for one crawling block at least, and collect all the crawled blocks.
+        if let Some(crawled) = self.in_flight.next().await {
+            self.buffer_crawled(crawled?)?;
+            while let Some(Some(crawled)) = self.in_flight.next().now_or_never() {
+                self.buffer_crawled(crawled?)?;
+            }
+        } else if self.idle_blocks.is_empty() && !self.busy_blocks.is_empty() {
+            // all the block actors are busy with the timed-out blocks, wait for one of them.
+            let (_, i, _) = future::select_all(self.busy_blocks.values_mut()).await;
+            let index = *self
+                .busy_blocks
+                .keys()
+                .nth(i)
+                .expect("selected from busy blocks");
+            self.busy_blocks.remove(&index);
+            self.idle_blocks.push(index);
+        } else if let Some(at) = self.retry_at.values().min() {
+            // only the failed blocks are left, wait for the earliest one.
+            tokio::time::sleep_until(tokio::time::Instant::from_std(*at)).await;
+        }
+
+        // emit the contiguous blocks as batches, the tail of the window is emitted
+        // only when there is no more crawling block.
+        while self.reorder.contiguous_len() >= self.tuner.batch_size()
+            || (self.in_flight.is_empty() && self.reorder.contiguous_len() > 0)
+        {
+            self.emit_contiguous().await?;
+        }
+
+        if self.tuner.adjust(tuner::resident_memory()) {
+            log::info!(
+                target: "actor",
+                "Tune in-flight blocks = {}, batch size = {}",
+                self.tuner.in_flight(),
+                self.tuner.batch_size()
+            );
+        }
         Ok(())
     }
 
+    // Emit a batch of the contiguous blocks from the front of the window.
+    async fn emit_contiguous(&mut self) -> Result<(), ActorError> {
+        let blocks = self.reorder.pop_contiguous(self.tuner.batch_size());
+        let last = self.reorder.next() - 1;
+        if !blocks.is_empty() {
+            self.db.send(DbEnsurePartitions::new(last)).await??;
+            let len = blocks.len();
+            let start = Instant::now();
+            // the failed batch stops the scheduler, which resumes from the stored blocks.
+            self.metadata.send(BatchBlockMessage::new(blocks)).await??;
+            self.tuner.record_store(len, start.elapsed());
+        }
+        self.curr_block = last;
+        Ok(())
+    }
+
+    // Wait for the crawling blocks and emit the contiguous blocks of the window when shutting down,
+    // the failed blocks are not crawled again, so the blocks after them are left in the window.
+    async fn drain_window(&mut self) -> Result<(), ActorError> {
+        while let Some(crawled) = self.in_flight.next().await {
+            self.buffer_crawled(crawled?)?;
+        }
+        while self.reorder.contiguous_len() > 0 {
+            self.emit_contiguous().await?;
+        }
+        Ok(())
+    }
+
+    // Crawl the block by an idle block actor in the window, returns false if there is no idle one.
+    fn spawn_crawl(&mut self, num: u32) -> bool {
+        self.release_blocks();
+        let index = match self.idle_blocks.pop() {
+            Some(index) => index,
+            None => return false,
+        };
+        log::debug!(target: "actor", "BlockActor[{}] Crawling Block #{}", index, num);
+        let crawl = Self::crawl_with_retry(
+            self.blocks[index].clone(),
+            self.db.clone(),
+            self.config.retry,
+            num,
+        );
+        self.in_flight.push(tokio::task::spawn(async move {
+            let start = Instant::now();
+            let block = crawl.await;
+            (index, num, start.elapsed(), block)
+        }));
+        true
+    }
+
+    // Move the block actors that have finished their timed-out blocks back to the idle ones.
+    fn release_blocks(&mut self) {
+        let idle_blocks = &mut self.idle_blocks;
+        self.busy_blocks.retain(|index, stale| {
+            let released = stale.as_mut().now_or_never().is_some();
+            if released {
+                idle_blocks.push(*index);
+            }
+            !released
+        });
+    }
+
+    fn buffer_crawled(
+        &mut self,
+        (index, num, elapsed, result): CrawlResult<Block>,
+    ) -> Result<(), ActorError> {
+        let (block, stale) = result?;
+        match stale {
+            Some(stale) => {
+                log::warn!(target: "actor", "BlockActor[{}] is busy until Block #{} is executed", index, num);
+                self.busy_blocks.insert(index, stale);
+            }
+            None => self.idle_blocks.push(index),
+        }
+        self.tuner.record_execution(elapsed);
+        match block {
+            Some(block) => {
+                self.retry_rounds.remove(&num);
+                let size = block.size_hint();
+                self.reorder.insert(num, block, size);
+            }
+            None => {
+                // the failed block has been recorded, the window is held at it until it succeeds.
+                let rounds = self.retry_rounds.entry(num).or_default();
+                let backoff = Duration::from_millis(self.config.retry.backoff_ms)
+                    * 2u32.pow(cmp::min(*rounds, MAX_RETRY_ROUNDS_DOUBLING));
+                *rounds += 1;
+                log::warn!(
+                    target: "actor",
+                    "Crawl the failed Block #{} again after {:?} (round {})",
+                    num, backoff, rounds
+                );
+                self.retry_at.insert(num, Instant::now() + backoff);
+            }
+        }
+        Ok(())
+    }
+
+    fn is_window_active(&self) -> bool {
+        !self.in_flight.is_empty() || !self.reorder.is_empty() || !self.retry_at.is_empty()
+    }
+
+    // Discard the crawling and crawled blocks, and restart the window from the current block.
+    fn reset_window(&mut self) {
+        for crawling in self.in_flight.iter() {
+            crawling.abort();
+        }
+        self.in_flight = FuturesUnordered::new();
+        self.release_blocks();
+        let busy_blocks = &self.busy_blocks;
+        self.idle_blocks = (0..self.blocks.len())
+            .filter(|index| !busy_blocks.contains_key(index))
+            .collect();
+        self.reorder.reset(self.curr_block + 1);
+        self.next_crawl = self.curr_block + 1;
+        self.retry_at.clear();
+        self.retry_rounds.clear();
+    }
+
     // It's about to catch up with the latest finalized block (curr_block < finalized_block)
     //
     // curr_block                       curr_finalized_block
@@ -337,12 +671,11 @@ where
         // next_block (self.curr_block + 1) <= finalized_block
         let next_block = self.curr_block + 1;
         log::debug!(target: "actor", "BlockActor[0] Crawling Block #{}", next_block);
-        let block = self
-            .crawl_block(next_block)
-            .await?
-            .expect("finalized block must exist");
-        self.metadata.send(block).await?;
-        self.curr_block = next_block;
+        // the failed block is recorded, and crawled again in the next tick.
+        if let Some(block) = self.crawl_block(next_block).await? {
+            self.metadata.send(block).await??;
+            self.curr_block = next_block;
+        }
         Ok(())
     }
 
@@ -356,11 +689,17 @@ where
     //    |                                       |
     //    +---------------------------------------+
     //               queue (length >= 1)
-    async fn tick_one_has_caught_up(&mut self) -> Result<(), ActorError> {
+    //
+    // The blocks after the `head` are not crawled until the head moves forward.
+    async fn tick_one_has_caught_up(&mut self, head: Option<u32>) -> Result<(), ActorError> {
         loop {
             // self.curr_block >= finalized_block, next_block (self.curr_block + 1) > finalized_block
             // so next block is not finalized block.
             let next_block = self.curr_block + 1;
+            if matches!(head, Some(head) if next_block > head) {
+                tokio::time::sleep(Duration::from_millis(self.config.interval_ms)).await;
+                break;
+            }
             log::debug!(target: "actor", "BlockActor[0] Crawling Block #{}", next_block);
 
             if let Some(block) = self.crawl_block(next_block).await? {
@@ -379,15 +718,12 @@ where
                         // fix issue https://github.com/patractlabs/archive/issues/62
 
                         // curr_block <= curr_finalized_block
-                        // re-construct queue
-                        let finalized_block = self
-                            .crawl_block(curr_finalized_block)
-                            .await?
-                            .expect("finalized block must exist");
-                        let finalized_header = finalized_block.inner.block.header();
+                        // re-construct queue, only the header is needed.
+                        let finalized_header = self
+                            .header(curr_finalized_block)?
+                            .ok_or(ActorError::MissingBlock(curr_finalized_block))?;
                         self.queue.clear();
-                        self.queue
-                            .insert(curr_finalized_block, finalized_header.clone());
+                        self.queue.insert(curr_finalized_block, finalized_header);
                         // reset the curr_block
                         self.curr_block = curr_finalized_block;
                     }
@@ -398,11 +734,11 @@ where
                         self.curr_block, curr_finalized_block, self.log_queue(),
                     );
                     self.db
-                        .send(DbDeleteGtBlockNum::new(self.curr_block))
+                        .send(DbOrphanGtBlockNum::new(self.curr_block))
                         .await??;
                 } else {
                     // the block is valid
-                    self.metadata.send(block.clone()).await?;
+                    self.metadata.send(block.clone()).await??;
                     self.queue.insert(next_block, next_header.clone());
                     self.curr_block = next_block;
                     log::info!(target: "actor", "Block Queue: {}", self.log_queue());
@@ -416,9 +752,149 @@ where
         Ok(())
     }
 
+    // Archive the blocks within the range `[from, min(from + max_block_load - 1, to)]`,
+    // the blocks are upserted without touching the blocks outside the range.
+    async fn backfill(&mut self, from: u32, to: u32) -> Result<u32, ActorError> {
+        let to = cmp::min(from.saturating_add(self.config.max_block_load - 1), to);
+        self.db.send(DbEnsurePartitions::new(to)).await??;
+
+        let fut = (from..=to)
+            .map(|i| {
+                let index = (i % self.config.max_block_load) as usize;
+                log::debug!(target: "actor", "BlockActor[{}] Backfilling Block #{}", index, i);
+                self.crawl_block_with_retry(index, i)
+            })
+            .collect::<Vec<_>>();
+        let results = futures::future::join_all(fut).await;
+        let mut blocks = Vec::with_capacity(results.len());
+        for (num, result) in (from..=to).zip(results) {
+            blocks.push(result?.ok_or(ActorError::MissingBlock(num))?);
+        }
+        self.metadata.send(BatchBlockMessage::new(blocks)).await??;
+        log::info!(target: "actor", "Backfill Block #{} ~ #{}", from, to);
+        Ok(to)
+    }
+
+    // Re-crawl the blocks within the range `[from, to]` that are missing or have no main storage,
+    // the range is limited to the archived finalized blocks. Returns the number of re-crawled blocks.
+    //
+    // The blocks archived in the fast mode have no main storage, they are only re-crawled in the
+    // full mode.
+    async fn repair(&mut self, from: Option<u32>, to: Option<u32>) -> Result<u32, ActorError> {
+        let (_best_num, finalized_num) = self.best_and_finalized().await?;
+        let max = match self.db.send(DbMaxBlock).await?? {
+            Some(max) => max,
+            None => return Ok(0),
+        };
+        let from = from.unwrap_or(self.unchecked_block);
+        let to = to.map_or(finalized_num, |to| cmp::min(to, finalized_num));
+        let to = cmp::min(to, max);
+        if from > to {
+            return Ok(0);
+        }
+
+        let mut ranges = self.db.send(DbBlockGaps::new(from, to)).await??;
+        let blocks = match self.config.mode {
+            SyncMode::Full => {
+                self.db
+                    .send(DbBlocksWithoutMainStorage::new(from, to))
+                    .await??
+            }
+            SyncMode::Fast => Vec::new(),
+        };
+        for block in blocks {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == block => *end = block,
+                _ => ranges.push((block, block)),
+            }
+        }
+        ranges.sort_unstable();
+
+        let mut repaired = 0;
+        for (start, end) in ranges {
+            log::warn!(target: "actor", "🔧 Repair Block #{} ~ #{}", start, end);
+            let mut next = start;
+            while next <= end {
+                let last = self.backfill(next, end).await?;
+                repaired += last - next + 1;
+                if last == u32::MAX {
+                    break;
+                }
+                next = last + 1;
+            }
+        }
+        self.unchecked_block = cmp::max(self.unchecked_block, to.saturating_add(1));
+        Ok(repaired)
+    }
+
     async fn crawl_block(&self, num: u32) -> Result<Option<BlockMessage<Block>>, ActorError> {
-        let actor = self.blocks.first().expect("At least one block actor");
-        Ok(actor.send(CrawlBlock::new(num)).await?)
+        self.crawl_block_with_retry(0, num).await
+    }
+
+    // Crawl the block by the BlockActor[index] with the retry policy, the block that still fails
+    // after all attempts is recorded into db. Returns `None` if the block doesn't exist or fails.
+    async fn crawl_block_with_retry(
+        &self,
+        index: usize,
+        num: u32,
+    ) -> Result<Option<BlockMessage<Block>>, ActorError> {
+        // the next crawling of the timed-out block actor is queued behind the stale one.
+        let (block, _stale) = Self::crawl_with_retry(
+            self.blocks[index].clone(),
+            self.db.clone(),
+            self.config.retry,
+            num,
+        )
+        .await?;
+        Ok(block)
+    }
+
+    // The timed-out block is not sent to the block actor again, since the actor is still
+    // executing it, the pending reply is returned so that the caller knows when it's idle.
+    async fn crawl_with_retry(
+        block: Address<BlockActor<Block, Backend, Api>>,
+        db: Address<PostgresActor<Block>>,
+        retry: RetryConfig,
+        num: u32,
+    ) -> Result<(Option<BlockMessage<Block>>, Option<StaleReply>), ActorError> {
+        let max_attempts = cmp::max(retry.max_attempts, 1);
+        let mut backoff = Duration::from_millis(retry.backoff_ms);
+        let mut error = String::new();
+        let mut attempts = 0;
+        let mut stale = None;
+        for attempt in 1..=max_attempts {
+            attempts = attempt;
+            let mut crawl = block.send(CrawlBlock::new(num)).boxed();
+            match tokio::time::timeout(Duration::from_millis(retry.timeout_ms), &mut crawl).await {
+                Ok(result) => match result? {
+                    Ok(block) => return Ok((block, None)),
+                    Err(err) => error = err.to_string(),
+                },
+                Err(_) => {
+                    error = format!("Timeout after {}ms", retry.timeout_ms);
+                    stale = Some(crawl.map(|_| ()).boxed());
+                    break;
+                }
+            }
+            if attempt < max_attempts {
+                log::warn!(
+                    target: "actor",
+                    "Crawl Block #{} failed (attempt {}/{}), retry after {:?}: {}",
+                    num, attempt, max_attempts, backoff, error
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+
+        log::error!(target: "actor", "Crawl Block #{} failed after {} attempts: {}", num, attempts, error);
+        db.send(FailedBlockMessage {
+            block_num: num,
+            attempts,
+            error,
+        })
+        .await??;
+        Ok((None, stale))
     }
 }
 
@@ -446,7 +922,29 @@ where
         ctx: &mut Context<Self>,
     ) -> <Initialize as Message>::Result {
         match self.initialize().await {
-            Ok(()) => {}
+            Ok(()) => {
+                if let Some(interval_ms) = self.config.gap_check_interval_ms {
+                    let addr = ctx.address().expect("Actor is running");
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(Duration::from_millis(interval_ms)).await;
+                            match addr.send(Repair::default()).await {
+                                Ok(Ok(0)) => {}
+                                Ok(Ok(repaired)) => {
+                                    log::info!(target: "actor", "Repaired {} missing blocks", repaired)
+                                }
+                                Ok(Err(err)) => {
+                                    log::error!(target: "actor", "Scheduler repair error: {}", err)
+                                }
+                                Err(_) => {
+                                    log::error!(target: "actor", "Scheduler Actor Disconnected");
+                                    break;
+                                }
+                            }
+                        }
+                    });
+                }
+            }
             Err(err) => {
                 log::error!(target: "actor", "Initialize Scheduler Actor: {}", err);
                 ctx.stop();
@@ -465,10 +963,74 @@ where
         CoreApi<Block> + ApiExt<Block, StateBackend = StateBackendFor<Backend, Block>>,
 {
     async fn handle(&mut self, _: Tick, _ctx: &mut Context<Self>) -> <Tick as Message>::Result {
+        if self.draining {
+            return Ok(());
+        }
         self.tick().await
     }
 }
 
+#[async_trait::async_trait]
+impl<Block, Backend, Api> Handler<Backfill> for Scheduler<Block, Backend, Api>
+where
+    Block: BlockT,
+    Backend: backend::Backend<Block> + BlockBackend<Block> + 'static,
+    Api: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+    <Api as ProvideRuntimeApi<Block>>::Api:
+        CoreApi<Block> + ApiExt<Block, StateBackend = StateBackendFor<Backend, Block>>,
+{
+    async fn handle(
+        &mut self,
+        message: Backfill,
+        _ctx: &mut Context<Self>,
+    ) -> <Backfill as Message>::Result {
+        self.backfill(message.from, message.to).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<Block, Backend, Api> Handler<Repair> for Scheduler<Block, Backend, Api>
+where
+    Block: BlockT,
+    Backend: backend::Backend<Block> + BlockBackend<Block> + 'static,
+    Api: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+    <Api as ProvideRuntimeApi<Block>>::Api:
+        CoreApi<Block> + ApiExt<Block, StateBackend = StateBackendFor<Backend, Block>>,
+{
+    async fn handle(
+        &mut self,
+        message: Repair,
+        _ctx: &mut Context<Self>,
+    ) -> <Repair as Message>::Result {
+        if self.draining {
+            return Ok(0);
+        }
+        self.repair(message.from, message.to).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<Block, Backend, Api> Handler<Flush> for Scheduler<Block, Backend, Api>
+where
+    Block: BlockT,
+    Backend: backend::Backend<Block> + BlockBackend<Block> + 'static,
+    Api: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+    <Api as ProvideRuntimeApi<Block>>::Api:
+        CoreApi<Block> + ApiExt<Block, StateBackend = StateBackendFor<Backend, Block>>,
+{
+    async fn handle(&mut self, _: Flush, _ctx: &mut Context<Self>) -> <Flush as Message>::Result {
+        log::info!(target: "actor", "Scheduler stops scheduling new blocks (curr #{})", self.curr_block);
+        self.draining = true;
+        // the drain is bounded by the timeout of the caller.
+        match self.drain_window().await {
+            Ok(()) => {
+                log::info!(target: "actor", "Scheduler drained the window (curr #{})", self.curr_block)
+            }
+            Err(err) => log::error!(target: "actor", "Scheduler drain error: {}", err),
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl<Block, Backend, Api> Handler<Die> for Scheduler<Block, Backend, Api>
 where
@@ -478,6 +1040,9 @@ where
 {
     async fn handle(&mut self, _: Die, ctx: &mut Context<Self>) -> <Die as Message>::Result {
         log::info!(target: "actor", "Stopping Scheduler Actor");
+        for crawling in self.in_flight.iter() {
+            crawling.abort();
+        }
         for (index, block) in self.blocks.iter().enumerate() {
             if let Err(_) = block.send(Die).await {
                 log::error!(target: "actor", "Stop Block[{}] Actor But Disconnected", index);
@@ -491,3 +1056,38 @@ where
         ctx.stop();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follow_finalized_and_best() {
+        assert_eq!(follow_head(FollowMode::Finalized, 100, 90), Some(90));
+        assert_eq!(follow_head(FollowMode::Finalized, 90, 90), Some(90));
+        assert_eq!(follow_head(FollowMode::Best, 100, 90), None);
+        assert_eq!(follow_head(FollowMode::Best, 0, 0), None);
+    }
+
+    #[test]
+    fn follow_best_with_depth() {
+        // the blocks with enough confirmations.
+        assert_eq!(follow_head(FollowMode::BestWithDepth(5), 100, 90), Some(95));
+        assert_eq!(
+            follow_head(FollowMode::BestWithDepth(10), 100, 90),
+            Some(90)
+        );
+        // never behind the finalized block.
+        assert_eq!(
+            follow_head(FollowMode::BestWithDepth(20), 100, 90),
+            Some(90)
+        );
+        // the depth deeper than the chain.
+        assert_eq!(follow_head(FollowMode::BestWithDepth(20), 10, 0), Some(0));
+        // no confirmation is required.
+        assert_eq!(
+            follow_head(FollowMode::BestWithDepth(0), 100, 90),
+            Some(100)
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::{cmp, fs, time::Duration};
+
+use crate::config::AdaptiveConfig;
+
+// The weight of the latest sample in the moving average of latency.
+const EWMA_ALPHA: f64 = 0.2;
+// The expected time of storing a batch, the batch size is tuned to approach it.
+const TARGET_BATCH_SECS: f64 = 1.0;
+
+/// Tunes the number of in-flight blocks and the batch size within the bounds,
+/// according to the latency of executing and storing blocks and the process memory.
+pub struct Tuner {
+    config: AdaptiveConfig,
+    in_flight: u32,
+    batch_size: u32,
+    // the moving average of the seconds of executing a block.
+    exec_secs: Option<f64>,
+    // the moving average of the seconds of storing a block.
+    store_secs: Option<f64>,
+}
+
+impl Tuner {
+    /// Creates a tuner, the in-flight blocks and batch size are fixed to `max_block_load`
+    /// if the adaptive config isn't provided.
+    pub fn new(config: Option<AdaptiveConfig>, max_block_load: u32) -> Self {
+        let mut config = config.unwrap_or(AdaptiveConfig {
+            min_in_flight: max_block_load,
+            max_in_flight: max_block_load,
+            min_batch_size: max_block_load,
+            max_batch_size: max_block_load,
+            max_memory_mb: None,
+        });
+        config.min_in_flight = cmp::max(config.min_in_flight, 1);
+        config.max_in_flight = cmp::max(config.max_in_flight, config.min_in_flight);
+        config.min_batch_size = cmp::max(config.min_batch_size, 1);
+        config.max_batch_size = cmp::max(config.max_batch_size, config.min_batch_size);
+        let in_flight = max_block_load.clamp(config.min_in_flight, config.max_in_flight);
+        let batch_size = max_block_load.clamp(config.min_batch_size, config.max_batch_size);
+        Self {
+            config,
+            in_flight,
+            batch_size,
+            exec_secs: None,
+            store_secs: None,
+        }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight as usize
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size as usize
+    }
+
+    pub fn record_execution(&mut self, elapsed: Duration) {
+        self.exec_secs = Some(ewma(self.exec_secs, elapsed.as_secs_f64()));
+    }
+
+    pub fn record_store(&mut self, blocks: usize, elapsed: Duration) {
+        if blocks > 0 {
+            let secs = elapsed.as_secs_f64() / blocks as f64;
+            self.store_secs = Some(ewma(self.store_secs, secs));
+        }
+    }
+
+    /// Adjusts the in-flight blocks and batch size, returns true if any of them is changed.
+    pub fn adjust(&mut self, memory_bytes: Option<u64>) -> bool {
+        let (in_flight, batch_size) = (self.in_flight, self.batch_size);
+        let over_memory = match (self.config.max_memory_mb, memory_bytes) {
+            (Some(max), Some(memory)) => memory > max * 1024 * 1024,
+            _ => false,
+        };
+
+        if over_memory {
+            // back off quickly when the memory is under pressure.
+            self.in_flight = cmp::max(self.in_flight / 2, self.config.min_in_flight);
+            self.batch_size = cmp::max(self.batch_size / 2, self.config.min_batch_size);
+        } else if let (Some(exec_secs), Some(store_secs)) = (self.exec_secs, self.store_secs) {
+            // add an in-flight block if the execution can't keep up with the storing, and vice versa.
+            let exec_rate = self.in_flight as f64 / exec_secs.max(f64::EPSILON);
+            let store_rate = 1.0 / store_secs.max(f64::EPSILON);
+            if exec_rate < store_rate {
+                self.in_flight = cmp::min(self.in_flight + 1, self.config.max_in_flight);
+            } else {
+                self.in_flight =
+                    cmp::max(self.in_flight.saturating_sub(1), self.config.min_in_flight);
+            }
+            // the blocks with huge storage changes are stored in smaller batches.
+            let batch_size = (TARGET_BATCH_SECS / store_secs.max(f64::EPSILON)) as u32;
+            self.batch_size =
+                batch_size.clamp(self.config.min_batch_size, self.config.max_batch_size);
+        }
+        (in_flight, batch_size) != (self.in_flight, self.batch_size)
+    }
+}
+
+fn ewma(average: Option<f64>, sample: f64) -> f64 {
+    match average {
+        Some(average) => average + EWMA_ALPHA * (sample - average),
+        None => sample,
+    }
+}
+
+/// Returns the resident memory of the process, only supported on linux.
+pub fn resident_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB");
+    kb.trim().parse::<u64>().ok().map(|kb| kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AdaptiveConfig {
+        AdaptiveConfig {
+            min_in_flight: 1,
+            max_in_flight: 8,
+            min_batch_size: 1,
+            max_batch_size: 1000,
+            max_memory_mb: Some(1024),
+        }
+    }
+
+    #[test]
+    fn fixed_without_adaptive_config() {
+        let mut tuner = Tuner::new(None, 10);
+        tuner.record_execution(Duration::from_secs(1));
+        tuner.record_store(10, Duration::from_millis(10));
+        assert!(!tuner.adjust(Some(u64::MAX)));
+        assert_eq!((tuner.in_flight(), tuner.batch_size()), (10, 10));
+    }
+
+    #[test]
+    fn tune_by_latency_and_memory() {
+        let mut tuner = Tuner::new(Some(config()), 4);
+        // slow execution, fast storing
+        tuner.record_execution(Duration::from_millis(100));
+        tuner.record_store(100, Duration::from_millis(100));
+        assert!(tuner.adjust(Some(0)));
+        assert_eq!((tuner.in_flight(), tuner.batch_size()), (5, 1000));
+
+        // memory pressure
+        assert!(tuner.adjust(Some(2048 * 1024 * 1024)));
+        assert_eq!((tuner.in_flight(), tuner.batch_size()), (2, 500));
+
+        // fast execution, slow storing
+        let mut tuner = Tuner::new(Some(config()), 4);
+        tuner.record_execution(Duration::from_millis(1));
+        tuner.record_store(1, Duration::from_millis(500));
+        assert!(tuner.adjust(None));
+        assert_eq!((tuner.in_flight(), tuner.batch_size()), (3, 2));
+    }
+}
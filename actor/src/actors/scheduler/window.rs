@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+/// The ordered reassembly buffer of the catch-up window, the blocks may be crawled out of order,
+/// but they are emitted in order of the block number, the emitting is held at a missing block.
+pub struct ReorderBuffer<T> {
+    // the number of the next block to be emitted.
+    next: u32,
+    buffer: BTreeMap<u32, (T, usize)>,
+    // the total size of the buffered blocks.
+    bytes: usize,
+}
+
+impl<T> ReorderBuffer<T> {
+    pub fn new(next: u32) -> Self {
+        Self {
+            next,
+            buffer: BTreeMap::new(),
+            bytes: 0,
+        }
+    }
+
+    pub fn reset(&mut self, next: u32) {
+        self.next = next;
+        self.buffer.clear();
+        self.bytes = 0;
+    }
+
+    pub fn next(&self) -> u32 {
+        self.next
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn insert(&mut self, num: u32, item: T, size: usize) {
+        debug_assert!(num >= self.next, "block #{} has been emitted", num);
+        self.bytes += size;
+        if let Some((_, old_size)) = self.buffer.insert(num, (item, size)) {
+            self.bytes -= old_size;
+        }
+    }
+
+    /// Returns the number of the contiguous blocks from the next block to be emitted.
+    pub fn contiguous_len(&self) -> usize {
+        self.buffer
+            .keys()
+            .zip(self.next..)
+            .take_while(|(num, expected)| **num == *expected)
+            .count()
+    }
+
+    /// Pops at most `max` contiguous blocks from the front.
+    pub fn pop_contiguous(&mut self, max: usize) -> Vec<T> {
+        let mut items = Vec::new();
+        for _ in 0..max {
+            match self.buffer.remove(&self.next) {
+                Some((item, size)) => {
+                    self.bytes -= size;
+                    self.next += 1;
+                    items.push(item);
+                }
+                None => break,
+            }
+        }
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emit_blocks_in_order() {
+        let mut buffer = ReorderBuffer::new(1);
+        buffer.insert(3, 3, 30);
+        buffer.insert(2, 2, 20);
+        assert_eq!(buffer.contiguous_len(), 0);
+        assert!(buffer.pop_contiguous(10).is_empty());
+
+        buffer.insert(1, 1, 10);
+        buffer.insert(5, 5, 50);
+        assert_eq!(buffer.contiguous_len(), 3);
+        assert_eq!(buffer.bytes(), 110);
+        assert_eq!(buffer.pop_contiguous(2), vec![1, 2]);
+        assert_eq!(buffer.next(), 3);
+        assert_eq!(buffer.pop_contiguous(10), vec![3]);
+        assert_eq!(buffer.next(), 4);
+        assert_eq!(buffer.bytes(), 50);
+
+        // the emitting is held at the missing block until it's crawled again.
+        assert_eq!(buffer.contiguous_len(), 0);
+        assert!(buffer.pop_contiguous(10).is_empty());
+        assert_eq!(buffer.next(), 4);
+        buffer.insert(4, 4, 40);
+        assert_eq!(buffer.pop_contiguous(10), vec![4, 5]);
+        assert_eq!(buffer.next(), 6);
+        assert_eq!(buffer.bytes(), 0);
+
+        buffer.reset(10);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.bytes(), 0);
+        assert_eq!(buffer.next(), 10);
+    }
+}
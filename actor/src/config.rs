@@ -11,6 +11,8 @@ pub struct ActorConfig {
     pub dispatcher: Option<DispatcherConfig>,
     pub genesis: Storage,
     pub scheduler: SchedulerConfig,
+    pub supervisor: SupervisorConfig,
+    pub mailbox: MailboxConfig,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -18,10 +20,183 @@ pub struct SchedulerConfig {
     pub start_block: Option<u32>,
     pub max_block_load: u32,
     pub interval_ms: u64,
+    #[serde(default)]
+    pub mode: SyncMode,
+    // Which head of the chain the scheduler follows after catching up with the finalized block.
+    #[serde(default)]
+    pub follow: FollowMode,
+    // Archive the blocks within the range and exit, instead of following the chain.
+    pub backfill: Option<BackfillConfig>,
+    // Re-crawl the missing blocks within the range and exit, instead of following the chain.
+    pub repair: Option<RepairConfig>,
+    // The interval of checking the missing blocks while following the chain, disabled if `None`.
+    pub gap_check_interval_ms: Option<u64>,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    // The memory bound (MiB) of the crawled blocks waiting to be stored when catching up.
+    pub window_memory_mb: Option<usize>,
+    // Tune the in-flight blocks and batch size at runtime, fixed to `max_block_load` if `None`.
+    pub adaptive: Option<AdaptiveConfig>,
+}
+
+/// The bounds of tuning the concurrency of crawling blocks and the size of storing batches.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct AdaptiveConfig {
+    pub min_in_flight: u32,
+    pub max_in_flight: u32,
+    pub min_batch_size: u32,
+    pub max_batch_size: u32,
+    // Back off when the resident memory (MiB) of the process exceeds it.
+    pub max_memory_mb: Option<u64>,
+}
+
+/// Whether to execute the blocks when crawling them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncMode {
+    // Execute the blocks, and archive the blocks with their storage changes.
+    Full,
+    // Don't execute the blocks, only archive the headers, extrinsics and justifications.
+    // The runtime is reused until the `RuntimeEnvironmentUpdated` digest, so the state is only
+    // read at the runtime upgrades. The storage changes can be repaired later in the full mode.
+    Fast,
+}
+
+impl Default for SyncMode {
+    fn default() -> Self {
+        Self::Full
+    }
+}
+
+/// Which head of the chain the blocks are archived and dispatched up to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FollowMode {
+    // Only archive the finalized blocks, the archived blocks are never rolled back.
+    Finalized,
+    // Archive the blocks up to the best block as soon as they are imported,
+    // the blocks are rolled back when the best chain is reorganized.
+    Best,
+    // Archive the blocks that have `n` confirmations on the best chain (never behind the finalized block),
+    // only the reorganization deeper than `n` blocks rolls back the archived blocks.
+    BestWithDepth(u32),
+}
+
+impl Default for FollowMode {
+    fn default() -> Self {
+        Self::Best
+    }
+}
+
+/// The retry policy of crawling a block, the block that still fails after all attempts
+/// is recorded into the `failed_block` table, and crawled again after the backoff without
+/// archiving the blocks after it.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct RetryConfig {
+    pub max_attempts: u32,
+    // The backoff before the next attempt, it's doubled after every failed attempt.
+    pub backoff_ms: u64,
+    // The timeout of crawling (executing) a block.
+    pub timeout_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff_ms: 1000,
+            timeout_ms: 60_000,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct BackfillConfig {
+    pub from: u32,
+    pub to: u32,
+}
+
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RepairConfig {
+    pub from: Option<u32>,
+    pub to: Option<u32>,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SupervisorConfig {
+    // The process exits with error when the actors are restarted more than `max_restarts` times.
+    pub max_restarts: u32,
+    // The interval of checking if the actors are alive.
+    pub interval_ms: u64,
+    // The maximum time of waiting for the in-flight blocks to be handled when shutting down.
+    pub drain_timeout_ms: u64,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            max_restarts: 3,
+            interval_ms: 1000,
+            drain_timeout_ms: 30_000,
+        }
+    }
+}
+
+/// The mailbox capacities of the actors, the sender waits when the mailbox is full,
+/// so that the block execution slows down to the speed of the slowest sink.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MailboxConfig {
+    pub scheduler: usize,
+    pub best_and_finalized: usize,
+    pub block: usize,
+    pub metadata: usize,
+    pub postgres: usize,
+    pub kafka: usize,
+}
+
+impl Default for MailboxConfig {
+    fn default() -> Self {
+        Self {
+            scheduler: 16,
+            best_and_finalized: 4,
+            block: 2,
+            metadata: 4,
+            postgres: 4,
+            kafka: 16,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DispatcherConfig {
+    #[serde(default)]
+    pub relay: RelayConfig,
     pub kafka: Option<KafkaConfig>,
     // others
 }
+
+/// The relay of every sink publishes the outbox from the committed offset of the sink.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelayConfig {
+    // The number of outbox records that are fetched at a time.
+    pub batch_size: u32,
+    // The interval of polling the outbox, the relay is woken up earlier when new records are committed.
+    pub interval_ms: u64,
+    // The backoff before retrying the failed sink, it's doubled after every failure up to `max_backoff_ms`.
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 100,
+            interval_ms: 1000,
+            backoff_ms: 1000,
+            max_backoff_ms: 60_000,
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use codec::{Compact, Decode};
+use scale_info::TypeDef;
+use serde_json::Value;
+
+use sp_core::hashing::twox_128;
+
+use archive_postgres::EventModel;
+
+use super::{
+    value::{decode_fields, decode_value, from_hex, read_variant},
+    DecodeError, Decoder,
+};
+
+/// Returns the storage key of `System.Events`.
+pub fn system_events_key() -> Vec<u8> {
+    [twox_128(b"System"), twox_128(b"Events")].concat()
+}
+
+/// The decoded `EventRecord`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub index: u32,
+    // `ApplyExtrinsic`, `Finalization` or `Initialization`.
+    pub phase: String,
+    pub extrinsic_index: Option<u32>,
+    pub pallet: String,
+    pub variant: String,
+    pub topics: Vec<Vec<u8>>,
+    pub fields: Value,
+}
+
+impl Event {
+    pub fn into_model(self, block_num: u32, block_hash: Vec<u8>) -> EventModel {
+        EventModel {
+            block_num,
+            block_hash,
+            event_index: self.index,
+            phase: self.phase,
+            extrinsic_index: self.extrinsic_index,
+            pallet: self.pallet,
+            variant: self.variant,
+            topics: self.topics,
+            fields: self.fields,
+        }
+    }
+}
+
+impl Decoder {
+    /// Decodes the value of `System.Events` into events,
+    /// returns nothing if the metadata doesn't contain `System.Events`.
+    pub fn decode_events(&self, data: &[u8]) -> Result<Vec<Event>, DecodeError> {
+        let ty = match self.events_ty {
+            Some(ty) => ty,
+            None => return Ok(Vec::new()),
+        };
+        let record_ty = match self.resolve(ty)?.type_def() {
+            TypeDef::Sequence(sequence) => sequence.type_param().id(),
+            _ => return Err(DecodeError::UnexpectedType(ty, "sequence")),
+        };
+        let record_fields = match self.resolve(record_ty)?.type_def() {
+            TypeDef::Composite(composite) => composite.fields(),
+            _ => return Err(DecodeError::UnexpectedType(record_ty, "struct")),
+        };
+
+        let input = &mut &data[..];
+        let len = Compact::<u32>::decode(input)?.0;
+        let mut events = Vec::new();
+        for index in 0..len {
+            let mut event = Event {
+                index,
+                phase: String::new(),
+                extrinsic_index: None,
+                pallet: String::new(),
+                variant: String::new(),
+                topics: Vec::new(),
+                fields: Value::Null,
+            };
+            for field in record_fields {
+                let ty = field.ty().id();
+                match field.name().map(String::as_str) {
+                    Some("phase") => {
+                        let (phase, extrinsic_index) =
+                            into_phase(decode_value(&self.types, ty, input)?);
+                        event.phase = phase;
+                        event.extrinsic_index = extrinsic_index;
+                    }
+                    Some("event") => {
+                        // the outer enum of runtime, the variants are pallets.
+                        let pallet = read_variant(&self.types, ty, input)?;
+                        let pallet_ty = match pallet.fields() {
+                            [field] => field.ty().id(),
+                            _ => return Err(DecodeError::UnexpectedType(ty, "runtime event")),
+                        };
+                        let variant = read_variant(&self.types, pallet_ty, input)?;
+                        event.pallet = pallet.name().clone();
+                        event.variant = variant.name().clone();
+                        event.fields = decode_fields(&self.types, variant.fields(), input)?;
+                    }
+                    Some("topics") => {
+                        let topics = decode_value(&self.types, ty, input)?;
+                        event.topics = topics
+                            .as_array()
+                            .map(|topics| topics.iter().filter_map(from_hex).collect())
+                            .unwrap_or_default();
+                    }
+                    _ => {
+                        decode_value(&self.types, ty, input)?;
+                    }
+                }
+            }
+            events.push(event);
+        }
+        if !input.is_empty() {
+            return Err(DecodeError::TrailingBytes(input.len()));
+        }
+        Ok(events)
+    }
+}
+
+// `ApplyExtrinsic(u32)` is decoded into `{"ApplyExtrinsic": u32}`,
+// and `Finalization`/`Initialization` are decoded into their names.
+fn into_phase(phase: Value) -> (String, Option<u32>) {
+    match phase {
+        Value::Object(map) => map
+            .into_iter()
+            .next()
+            .map(|(name, index)| (name, index.as_u64().map(|index| index as u32)))
+            .unwrap_or_default(),
+        Value::String(name) => (name, None),
+        other => (other.to_string(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::fixture::{self, BalancesEvent, EventRecord, Phase};
+    use codec::Encode;
+    use serde_json::json;
+
+    fn transfer(phase: Phase, topics: Vec<[u8; 32]>) -> EventRecord {
+        EventRecord {
+            phase,
+            event: fixture::Event::Balances(BalancesEvent::Transfer {
+                from: [1; 32],
+                to: [2; 32],
+                amount: 10,
+            }),
+            topics,
+        }
+    }
+
+    fn transfer_fields() -> Value {
+        json!({
+            "from": format!("0x{}", hex::encode([1; 32])),
+            "to": format!("0x{}", hex::encode([2; 32])),
+            "amount": "10",
+        })
+    }
+
+    #[test]
+    fn decode_event_records() {
+        let records = vec![
+            transfer(Phase::ApplyExtrinsic(1), vec![[3; 32]]),
+            transfer(Phase::Finalization, vec![]),
+        ];
+        let events = fixture::decoder().decode_events(&records.encode()).unwrap();
+        assert_eq!(
+            events,
+            vec![
+                Event {
+                    index: 0,
+                    phase: "ApplyExtrinsic".into(),
+                    extrinsic_index: Some(1),
+                    pallet: "Balances".into(),
+                    variant: "Transfer".into(),
+                    topics: vec![vec![3; 32]],
+                    fields: transfer_fields(),
+                },
+                Event {
+                    index: 1,
+                    phase: "Finalization".into(),
+                    extrinsic_index: None,
+                    pallet: "Balances".into(),
+                    variant: "Transfer".into(),
+                    topics: vec![],
+                    fields: transfer_fields(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_invalid_event_records() {
+        let decoder = fixture::decoder();
+        let mut encoded = vec![transfer(Phase::Initialization, vec![])].encode();
+        encoded.push(0);
+        assert!(matches!(
+            decoder.decode_events(&encoded),
+            Err(DecodeError::TrailingBytes(1))
+        ));
+
+        // the index of the pallet variant is unknown.
+        let mut encoded = vec![transfer(Phase::Initialization, vec![])].encode();
+        encoded[2] = 6;
+        assert!(matches!(
+            decoder.decode_events(&encoded),
+            Err(DecodeError::VariantNotFound(_, 6))
+        ));
+    }
+}
@@ -0,0 +1,310 @@
+use codec::{Compact, Decode};
+use frame_metadata::v14::ExtrinsicMetadata;
+use scale_info::{form::PortableForm, PortableRegistry};
+use serde_json::Value;
+
+use sp_core::hashing::blake2_256;
+
+use archive_postgres::ExtrinsicModel;
+
+use super::{
+    value::{decode_fields, decode_value, from_hex, read_variant},
+    DecodeError, Decoder,
+};
+
+/// The types that an extrinsic consists of.
+pub(super) struct ExtrinsicTypes {
+    version: u8,
+    address_ty: u32,
+    signature_ty: u32,
+    call_ty: u32,
+    // (identifier, type) of the signed extensions in order.
+    signed_extensions: Vec<(String, u32)>,
+}
+
+impl ExtrinsicTypes {
+    pub(super) fn new(
+        types: &PortableRegistry,
+        extrinsic: &ExtrinsicMetadata<PortableForm>,
+    ) -> Option<Self> {
+        // `UncheckedExtrinsic<Address, Call, Signature, Extra>`
+        let params = types.resolve(extrinsic.ty.id())?.type_params();
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|param| param.name() == name)
+                .and_then(|param| param.ty())
+                .map(|ty| ty.id())
+        };
+        Some(Self {
+            version: extrinsic.version,
+            address_ty: param("Address")?,
+            signature_ty: param("Signature")?,
+            call_ty: param("Call")?,
+            signed_extensions: extrinsic
+                .signed_extensions
+                .iter()
+                .map(|extension| (extension.identifier.clone(), extension.ty.id()))
+                .collect(),
+        })
+    }
+}
+
+/// The decoded extrinsic, only `raw` is set besides the index, hash and signed flag
+/// if it can't be decoded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Extrinsic {
+    pub index: u32,
+    pub hash: Vec<u8>,
+    pub is_signed: bool,
+    pub signer: Option<Vec<u8>>,
+    pub nonce: Option<u32>,
+    // The decimal string of the tip.
+    pub tip: Option<String>,
+    pub era: Option<Value>,
+    pub pallet: Option<String>,
+    pub call: Option<String>,
+    pub args: Option<Value>,
+    pub raw: Option<Vec<u8>>,
+}
+
+impl Extrinsic {
+    /// Keeps the raw bytes of the extrinsic that can't be decoded.
+    pub fn undecoded(index: u32, encoded: &[u8]) -> Self {
+        let mut input = encoded;
+        let version = Compact::<u32>::decode(&mut input).and_then(|_| u8::decode(&mut input));
+        Self {
+            index,
+            hash: blake2_256(encoded).to_vec(),
+            is_signed: matches!(version, Ok(version) if version & 0b1000_0000 != 0),
+            signer: None,
+            nonce: None,
+            tip: None,
+            era: None,
+            pallet: None,
+            call: None,
+            args: None,
+            raw: Some(encoded.to_vec()),
+        }
+    }
+
+    pub fn into_model(self, block_num: u32, block_hash: Vec<u8>) -> ExtrinsicModel {
+        ExtrinsicModel {
+            block_num,
+            block_hash,
+            extrinsic_index: self.index,
+            hash: self.hash,
+            is_signed: self.is_signed,
+            signer: self.signer,
+            nonce: self.nonce,
+            tip: self.tip,
+            era: self.era,
+            pallet: self.pallet,
+            call: self.call,
+            args: self.args,
+            raw: self.raw,
+        }
+    }
+}
+
+impl Decoder {
+    /// Decodes the encoded extrinsic (with the length prefix) of the index in block.
+    pub fn decode_extrinsic(&self, index: u32, encoded: &[u8]) -> Result<Extrinsic, DecodeError> {
+        let types = self
+            .extrinsic
+            .as_ref()
+            .ok_or(DecodeError::UnsupportedMetadata)?;
+
+        let input = &mut &encoded[..];
+        let len = Compact::<u32>::decode(input)?.0 as usize;
+        if input.len() != len {
+            return Err(codec::Error::from("Invalid extrinsic length").into());
+        }
+        let version = u8::decode(input)?;
+        if version & 0b0111_1111 != types.version {
+            return Err(DecodeError::UnsupportedExtrinsic(version & 0b0111_1111));
+        }
+
+        let mut extrinsic = Extrinsic {
+            index,
+            hash: blake2_256(encoded).to_vec(),
+            is_signed: version & 0b1000_0000 != 0,
+            signer: None,
+            nonce: None,
+            tip: None,
+            era: None,
+            pallet: None,
+            call: None,
+            args: None,
+            raw: None,
+        };
+        if extrinsic.is_signed {
+            let address = decode_value(&self.types, types.address_ty, input)?;
+            extrinsic.signer = into_account(&address);
+            decode_value(&self.types, types.signature_ty, input)?;
+            for (identifier, ty) in &types.signed_extensions {
+                let value = decode_value(&self.types, *ty, input)?;
+                match identifier.as_str() {
+                    "CheckMortality" | "CheckEra" => extrinsic.era = Some(value),
+                    "CheckNonce" => extrinsic.nonce = value.as_u64().map(|nonce| nonce as u32),
+                    "ChargeTransactionPayment" => extrinsic.tip = into_decimal(&value),
+                    "ChargeAssetTxPayment" => {
+                        extrinsic.tip = value.get("tip").and_then(into_decimal)
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // the outer enum of runtime, the variants are pallets.
+        let pallet = read_variant(&self.types, types.call_ty, input)?;
+        let pallet_ty = match pallet.fields() {
+            [field] => field.ty().id(),
+            _ => return Err(DecodeError::UnexpectedType(types.call_ty, "runtime call")),
+        };
+        let call = read_variant(&self.types, pallet_ty, input)?;
+        let args = decode_fields(&self.types, call.fields(), input)?;
+        if !input.is_empty() {
+            return Err(DecodeError::TrailingBytes(input.len()));
+        }
+        extrinsic.pallet = Some(pallet.name().clone());
+        extrinsic.call = Some(call.name().clone());
+        extrinsic.args = Some(args);
+        Ok(extrinsic)
+    }
+}
+
+// The account of `AccountId` or `MultiAddress::{Id, Address32, Address20, Raw}`,
+// `MultiAddress::Index` has no account.
+fn into_account(address: &Value) -> Option<Vec<u8>> {
+    match address {
+        Value::Object(map) => map.values().next().and_then(from_hex),
+        other => from_hex(other),
+    }
+}
+
+fn into_decimal(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::fixture::{
+        self, Call, ChargeTransactionPayment, CheckNonce, Era, MultiAddress, SystemCall,
+    };
+    use codec::Encode;
+    use serde_json::json;
+
+    fn remark() -> Vec<u8> {
+        Call::System(SystemCall::remark { remark: vec![1, 2] }).encode()
+    }
+
+    fn signed() -> Vec<u8> {
+        let mut body = vec![0b1000_0100];
+        body.extend(MultiAddress::Id([1; 32]).encode());
+        body.extend([7u8; 64]);
+        body.extend(Era::Immortal.encode());
+        body.extend(CheckNonce(5).encode());
+        body.extend(ChargeTransactionPayment(100).encode());
+        body.extend(remark());
+        body.encode()
+    }
+
+    fn unsigned() -> Vec<u8> {
+        let mut body = vec![0b0000_0100];
+        body.extend(remark());
+        body.encode()
+    }
+
+    #[test]
+    fn decode_signed_extrinsic() {
+        let encoded = signed();
+        let extrinsic = fixture::decoder().decode_extrinsic(1, &encoded).unwrap();
+        assert_eq!(
+            extrinsic,
+            Extrinsic {
+                index: 1,
+                hash: blake2_256(&encoded).to_vec(),
+                is_signed: true,
+                signer: Some(vec![1; 32]),
+                nonce: Some(5),
+                tip: Some("100".into()),
+                era: Some(json!("Immortal")),
+                pallet: Some("System".into()),
+                call: Some("remark".into()),
+                args: Some(json!({ "remark": "0x0102" })),
+                raw: None,
+            }
+        );
+    }
+
+    #[test]
+    fn decode_unsigned_extrinsic() {
+        let encoded = unsigned();
+        let extrinsic = fixture::decoder().decode_extrinsic(0, &encoded).unwrap();
+        assert_eq!(
+            extrinsic,
+            Extrinsic {
+                index: 0,
+                hash: blake2_256(&encoded).to_vec(),
+                is_signed: false,
+                signer: None,
+                nonce: None,
+                tip: None,
+                era: None,
+                pallet: Some("System".into()),
+                call: Some("remark".into()),
+                args: Some(json!({ "remark": "0x0102" })),
+                raw: None,
+            }
+        );
+    }
+
+    #[test]
+    fn decode_invalid_extrinsic() {
+        let decoder = fixture::decoder();
+
+        let mut body = vec![0b0000_0101];
+        body.extend(remark());
+        assert!(matches!(
+            decoder.decode_extrinsic(0, &body.encode()),
+            Err(DecodeError::UnsupportedExtrinsic(5))
+        ));
+
+        let mut body = vec![0b0000_0100];
+        body.extend(remark());
+        body.push(0);
+        assert!(matches!(
+            decoder.decode_extrinsic(0, &body.encode()),
+            Err(DecodeError::TrailingBytes(1))
+        ));
+
+        // the length prefix doesn't match the length of the extrinsic.
+        let mut encoded = unsigned();
+        encoded.push(0);
+        assert!(matches!(
+            decoder.decode_extrinsic(0, &encoded),
+            Err(DecodeError::Codec(_))
+        ));
+    }
+
+    #[test]
+    fn keep_undecoded_extrinsic() {
+        let encoded = signed();
+        let extrinsic = Extrinsic::undecoded(2, &encoded);
+        assert_eq!(extrinsic.index, 2);
+        assert_eq!(extrinsic.hash, blake2_256(&encoded).to_vec());
+        assert!(extrinsic.is_signed);
+        assert_eq!(extrinsic.pallet, None);
+        assert_eq!(extrinsic.raw, Some(encoded));
+
+        let extrinsic = Extrinsic::undecoded(0, &[]);
+        assert!(!extrinsic.is_signed);
+        assert_eq!(extrinsic.raw, Some(vec![]));
+    }
+}
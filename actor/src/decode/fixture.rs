@@ -0,0 +1,182 @@
+//! A tiny V14 metadata for the decoding tests, it only contains the types used by the tests.
+
+#![allow(non_camel_case_types)]
+
+use std::marker::PhantomData;
+
+use codec::Encode;
+use frame_metadata::{
+    v14::{
+        ExtrinsicMetadata, PalletMetadata, PalletStorageMetadata, RuntimeMetadataV14,
+        SignedExtensionMetadata, StorageEntryMetadata, StorageEntryModifier, StorageEntryType,
+        StorageHasher,
+    },
+    RuntimeMetadataPrefixed,
+};
+use scale_info::{meta_type, TypeInfo};
+
+use super::Decoder;
+
+#[derive(Encode, TypeInfo)]
+pub enum Phase {
+    ApplyExtrinsic(u32),
+    Finalization,
+    Initialization,
+}
+
+#[derive(Encode, TypeInfo)]
+pub enum BalancesEvent {
+    #[codec(index = 2)]
+    Transfer {
+        from: [u8; 32],
+        to: [u8; 32],
+        amount: u128,
+    },
+}
+
+#[derive(Encode, TypeInfo)]
+pub enum Event {
+    #[codec(index = 5)]
+    Balances(BalancesEvent),
+}
+
+#[derive(Encode, TypeInfo)]
+pub struct EventRecord {
+    pub phase: Phase,
+    pub event: Event,
+    pub topics: Vec<[u8; 32]>,
+}
+
+#[derive(Encode, TypeInfo)]
+pub enum SystemCall {
+    #[codec(index = 1)]
+    remark { remark: Vec<u8> },
+}
+
+#[derive(Encode, TypeInfo)]
+pub enum Call {
+    #[codec(index = 0)]
+    System(SystemCall),
+}
+
+#[derive(Encode, TypeInfo)]
+pub enum MultiAddress {
+    Id([u8; 32]),
+}
+
+#[derive(Encode, TypeInfo)]
+pub enum Era {
+    Immortal,
+}
+
+#[derive(Encode, TypeInfo)]
+pub struct CheckNonce(#[codec(compact)] pub u32);
+
+#[derive(Encode, TypeInfo)]
+pub struct ChargeTransactionPayment(#[codec(compact)] pub u128);
+
+// only the type info of the extrinsic is used.
+#[allow(dead_code)]
+#[derive(TypeInfo)]
+pub struct UncheckedExtrinsic<Address, Call, Signature, Extra>(
+    PhantomData<(Address, Call, Signature, Extra)>,
+);
+
+type Extrinsic =
+    UncheckedExtrinsic<MultiAddress, Call, [u8; 64], (Era, CheckNonce, ChargeTransactionPayment)>;
+
+fn storage_entry(name: &'static str, ty: StorageEntryType) -> StorageEntryMetadata {
+    StorageEntryMetadata {
+        name,
+        modifier: StorageEntryModifier::Default,
+        ty,
+        default: vec![0],
+        docs: vec![],
+    }
+}
+
+fn pallet(name: &'static str, index: u8, entries: Vec<StorageEntryMetadata>) -> PalletMetadata {
+    PalletMetadata {
+        name,
+        storage: Some(PalletStorageMetadata {
+            prefix: name,
+            entries,
+        }),
+        calls: None,
+        event: None,
+        constants: vec![],
+        error: None,
+        index,
+    }
+}
+
+/// Returns the encoded metadata, which contains:
+/// - `System.Events`: `Vec<EventRecord>`
+/// - `System.Account`: `Blake2_128Concat([u8; 32]) => u32`
+/// - `Staking.ErasStakers`: `Twox64Concat(u32), Twox64Concat([u8; 32]) => u32`
+/// - `Staking.Opaque`: `Blake2_256(u32) => u32`
+pub fn metadata() -> Vec<u8> {
+    let system = pallet(
+        "System",
+        0,
+        vec![
+            storage_entry(
+                "Events",
+                StorageEntryType::Plain(meta_type::<Vec<EventRecord>>()),
+            ),
+            storage_entry(
+                "Account",
+                StorageEntryType::Map {
+                    hashers: vec![StorageHasher::Blake2_128Concat],
+                    key: meta_type::<[u8; 32]>(),
+                    value: meta_type::<u32>(),
+                },
+            ),
+        ],
+    );
+    let staking = pallet(
+        "Staking",
+        7,
+        vec![
+            storage_entry(
+                "ErasStakers",
+                StorageEntryType::Map {
+                    hashers: vec![StorageHasher::Twox64Concat, StorageHasher::Twox64Concat],
+                    key: meta_type::<(u32, [u8; 32])>(),
+                    value: meta_type::<u32>(),
+                },
+            ),
+            storage_entry(
+                "Opaque",
+                StorageEntryType::Map {
+                    hashers: vec![StorageHasher::Blake2_256],
+                    key: meta_type::<u32>(),
+                    value: meta_type::<u32>(),
+                },
+            ),
+        ],
+    );
+    let extension = |identifier, ty| SignedExtensionMetadata {
+        identifier,
+        ty,
+        additional_signed: meta_type::<()>(),
+    };
+    let extrinsic = ExtrinsicMetadata {
+        ty: meta_type::<Extrinsic>(),
+        version: 4,
+        signed_extensions: vec![
+            extension("CheckMortality", meta_type::<Era>()),
+            extension("CheckNonce", meta_type::<CheckNonce>()),
+            extension(
+                "ChargeTransactionPayment",
+                meta_type::<ChargeTransactionPayment>(),
+            ),
+        ],
+    };
+    let metadata = RuntimeMetadataV14::new(vec![system, staking], extrinsic, meta_type::<()>());
+    RuntimeMetadataPrefixed::from(metadata).encode()
+}
+
+pub fn decoder() -> Decoder {
+    Decoder::new(&metadata()).expect("fixture metadata is V14")
+}
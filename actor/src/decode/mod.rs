@@ -0,0 +1,84 @@
+//! Decodes the SCALE encoded data of blocks with the runtime metadata, only V14 is supported.
+
+mod event;
+mod extrinsic;
+#[cfg(test)]
+mod fixture;
+mod storage;
+mod value;
+
+use std::collections::HashMap;
+
+use codec::Decode;
+use frame_metadata::{v14::StorageEntryType, RuntimeMetadata, RuntimeMetadataPrefixed};
+use scale_info::{form::PortableForm, PortableRegistry, Type};
+
+pub use self::{
+    event::{system_events_key, Event},
+    extrinsic::Extrinsic,
+};
+
+use self::{extrinsic::ExtrinsicTypes, storage::StorageEntry};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error("{0}")]
+    Codec(#[from] codec::Error),
+    #[error("Metadata before V14 is unsupported")]
+    UnsupportedMetadata,
+    #[error("Type #{0} is not found in metadata")]
+    TypeNotFound(u32),
+    #[error("Variant index {1} of type #{0} is not found in metadata")]
+    VariantNotFound(u32, u8),
+    #[error("Type #{0} is not {1}")]
+    UnexpectedType(u32, &'static str),
+    #[error("Unsupported extrinsic version {0}")]
+    UnsupportedExtrinsic(u8),
+    #[error("{0} bytes are left after decoding")]
+    TrailingBytes(usize),
+}
+
+/// The decoder of a runtime version.
+pub struct Decoder {
+    types: PortableRegistry,
+    // The value type of `System.Events`.
+    events_ty: Option<u32>,
+    // The types of extrinsic, `None` if they are missing in the metadata.
+    extrinsic: Option<ExtrinsicTypes>,
+    // The storage entries of `Twox128(pallet prefix) ++ Twox128(storage entry name)`.
+    storage: HashMap<Vec<u8>, StorageEntry>,
+}
+
+impl Decoder {
+    pub fn new(metadata: &[u8]) -> Result<Self, DecodeError> {
+        let metadata = match RuntimeMetadataPrefixed::decode(&mut &metadata[..])?.1 {
+            RuntimeMetadata::V14(metadata) => metadata,
+            _ => return Err(DecodeError::UnsupportedMetadata),
+        };
+
+        let events_ty = metadata
+            .pallets
+            .iter()
+            .find(|pallet| pallet.name == "System")
+            .and_then(|pallet| pallet.storage.as_ref())
+            .and_then(|storage| storage.entries.iter().find(|entry| entry.name == "Events"))
+            .and_then(|entry| match &entry.ty {
+                StorageEntryType::Plain(ty) => Some(ty.id()),
+                StorageEntryType::Map { .. } => None,
+            });
+
+        let extrinsic = ExtrinsicTypes::new(&metadata.types, &metadata.extrinsic);
+        let storage = storage::storage_entries(&metadata.pallets, &metadata.types);
+
+        Ok(Self {
+            types: metadata.types,
+            events_ty,
+            extrinsic,
+            storage,
+        })
+    }
+
+    fn resolve(&self, ty: u32) -> Result<&Type<PortableForm>, DecodeError> {
+        self.types.resolve(ty).ok_or(DecodeError::TypeNotFound(ty))
+    }
+}
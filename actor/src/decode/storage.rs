@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use frame_metadata::v14::{PalletMetadata, StorageEntryType, StorageHasher};
+use scale_info::{form::PortableForm, PortableRegistry, TypeDef};
+use serde_json::Value;
+
+use sp_core::hashing::twox_128;
+
+use archive_postgres::{StorageKeyModel, StoragePrefixModel};
+
+use super::{value::decode_value, DecodeError, Decoder};
+
+/// The storage entry of a `Twox128(pallet prefix) ++ Twox128(storage entry name)` prefix.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct StorageEntry {
+    pallet: String,
+    item: String,
+    // The hashers and the types of map keys, empty for the plain storage.
+    keys: Vec<(StorageHasher, u32)>,
+}
+
+/// Builds the prefix registry from the storage entries of pallets.
+pub(super) fn storage_entries(
+    pallets: &[PalletMetadata<PortableForm>],
+    types: &PortableRegistry,
+) -> HashMap<Vec<u8>, StorageEntry> {
+    let mut entries = HashMap::new();
+    for pallet in pallets {
+        let storage = match &pallet.storage {
+            Some(storage) => storage,
+            None => continue,
+        };
+        let pallet_hash = twox_128(storage.prefix.as_bytes());
+        for entry in &storage.entries {
+            let keys = match &entry.ty {
+                StorageEntryType::Plain(_) => Vec::new(),
+                StorageEntryType::Map { hashers, key, .. } => {
+                    let key = key.id();
+                    // the key of the map with multiple hashers is a tuple of the keys.
+                    let key_tys = match types.resolve(key).map(|ty| ty.type_def()) {
+                        Some(TypeDef::Tuple(tuple)) if hashers.len() > 1 => {
+                            tuple.fields().iter().map(|field| field.id()).collect()
+                        }
+                        _ => vec![key],
+                    };
+                    hashers.iter().cloned().zip(key_tys).collect()
+                }
+            };
+            let prefix = [pallet_hash, twox_128(entry.name.as_bytes())].concat();
+            entries.insert(
+                prefix,
+                StorageEntry {
+                    pallet: pallet.name.clone(),
+                    item: entry.name.clone(),
+                    keys,
+                },
+            );
+        }
+    }
+    entries
+}
+
+impl Decoder {
+    /// Returns the names of all storage prefixes of the metadata.
+    pub fn storage_prefix_models(&self) -> Vec<StoragePrefixModel> {
+        self.storage
+            .iter()
+            .map(|(prefix, entry)| StoragePrefixModel {
+                prefix: prefix.clone(),
+                pallet: entry.pallet.clone(),
+                item: entry.item.clone(),
+            })
+            .collect()
+    }
+
+    /// Decodes the arguments of the map key, returns `None` for the plain storage or
+    /// the key whose prefix is unknown.
+    ///
+    /// The arguments hashed by the opaque hashers are decoded into `null`,
+    /// and the map with multiple hashers is decoded into an array of arguments.
+    pub fn decode_storage_key(&self, key: &[u8]) -> Result<Option<StorageKeyModel>, DecodeError> {
+        if key.len() < 32 {
+            return Ok(None);
+        }
+        let (prefix, rest) = key.split_at(32);
+        let entry = match self.storage.get(prefix) {
+            Some(entry) if !entry.keys.is_empty() => entry,
+            _ => return Ok(None),
+        };
+
+        let input = &mut &rest[..];
+        let mut args = Vec::with_capacity(entry.keys.len());
+        for (hasher, ty) in &entry.keys {
+            let arg = match hasher {
+                StorageHasher::Blake2_128 | StorageHasher::Twox128 => skip(input, 16)?,
+                StorageHasher::Blake2_256 | StorageHasher::Twox256 => skip(input, 32)?,
+                StorageHasher::Blake2_128Concat => {
+                    skip(input, 16)?;
+                    decode_value(&self.types, *ty, input)?
+                }
+                StorageHasher::Twox64Concat => {
+                    skip(input, 8)?;
+                    decode_value(&self.types, *ty, input)?
+                }
+                StorageHasher::Identity => decode_value(&self.types, *ty, input)?,
+            };
+            args.push(arg);
+        }
+        if !input.is_empty() {
+            return Err(DecodeError::TrailingBytes(input.len()));
+        }
+
+        let args = if args.len() == 1 {
+            args.remove(0)
+        } else {
+            Value::Array(args)
+        };
+        Ok(Some(StorageKeyModel {
+            key: key.to_vec(),
+            prefix: prefix.to_vec(),
+            args,
+        }))
+    }
+}
+
+// Skips the hash of the opaque hasher.
+fn skip(input: &mut &[u8], len: usize) -> Result<Value, DecodeError> {
+    if input.len() < len {
+        return Err(codec::Error::from("Not enough data to fill buffer").into());
+    }
+    *input = &input[len..];
+    Ok(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::{fixture, system_events_key};
+    use codec::Encode;
+    use serde_json::json;
+    use sp_core::hashing::{blake2_128, blake2_256, twox_64};
+
+    fn prefix(pallet: &str, item: &str) -> Vec<u8> {
+        [twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat()
+    }
+
+    fn account_key() -> Vec<u8> {
+        let account = [1u8; 32];
+        [
+            prefix("System", "Account"),
+            blake2_128(&account).to_vec(),
+            account.to_vec(),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn storage_prefixes() {
+        let mut models = fixture::decoder().storage_prefix_models();
+        models.sort_by(|a, b| (&a.pallet, &a.item).cmp(&(&b.pallet, &b.item)));
+        let names = models
+            .iter()
+            .map(|model| (model.pallet.as_str(), model.item.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                ("Staking", "ErasStakers"),
+                ("Staking", "Opaque"),
+                ("System", "Account"),
+                ("System", "Events"),
+            ]
+        );
+        assert_eq!(models[3].prefix, system_events_key());
+    }
+
+    #[test]
+    fn decode_unknown_keys() {
+        let decoder = fixture::decoder();
+        // shorter than the prefix.
+        assert_eq!(decoder.decode_storage_key(&[0; 31]).unwrap(), None);
+        // the plain storage.
+        assert_eq!(
+            decoder.decode_storage_key(&system_events_key()).unwrap(),
+            None
+        );
+        // the unknown prefix.
+        assert_eq!(decoder.decode_storage_key(&[0; 48]).unwrap(), None);
+    }
+
+    #[test]
+    fn decode_map_keys() {
+        let decoder = fixture::decoder();
+
+        let key = account_key();
+        assert_eq!(
+            decoder.decode_storage_key(&key).unwrap(),
+            Some(StorageKeyModel {
+                key: key.clone(),
+                prefix: prefix("System", "Account"),
+                args: json!(format!("0x{}", hex::encode([1; 32]))),
+            })
+        );
+
+        let (era, stash) = (7u32, [2u8; 32]);
+        let key = [
+            prefix("Staking", "ErasStakers"),
+            twox_64(&era.encode()).to_vec(),
+            era.encode(),
+            twox_64(&stash).to_vec(),
+            stash.to_vec(),
+        ]
+        .concat();
+        assert_eq!(
+            decoder.decode_storage_key(&key).unwrap(),
+            Some(StorageKeyModel {
+                key: key.clone(),
+                prefix: prefix("Staking", "ErasStakers"),
+                args: json!([7, format!("0x{}", hex::encode(stash))]),
+            })
+        );
+
+        let key = [
+            prefix("Staking", "Opaque"),
+            blake2_256(&era.encode()).to_vec(),
+        ]
+        .concat();
+        assert_eq!(
+            decoder.decode_storage_key(&key).unwrap(),
+            Some(StorageKeyModel {
+                key: key.clone(),
+                prefix: prefix("Staking", "Opaque"),
+                args: Value::Null,
+            })
+        );
+    }
+
+    #[test]
+    fn decode_invalid_map_keys() {
+        let decoder = fixture::decoder();
+
+        let mut key = account_key();
+        key.push(0);
+        assert!(matches!(
+            decoder.decode_storage_key(&key),
+            Err(DecodeError::TrailingBytes(1))
+        ));
+
+        // the hash of the opaque hasher is truncated.
+        let key = [prefix("Staking", "Opaque"), vec![0; 31]].concat();
+        assert!(matches!(
+            decoder.decode_storage_key(&key),
+            Err(DecodeError::Codec(_))
+        ));
+    }
+}
@@ -0,0 +1,237 @@
+use codec::{Compact, Decode};
+use scale_info::{form::PortableForm, Field, PortableRegistry, TypeDef, TypeDefPrimitive, Variant};
+use serde_json::{Map, Value};
+
+use super::DecodeError;
+
+/// Decodes the SCALE encoded value of type `ty` into JSON.
+///
+/// - the struct with named fields is decoded into an object, with unnamed fields into an array,
+///   and the struct with only one unnamed field is decoded into the value of the field.
+/// - the enum variant without fields is decoded into its name, otherwise `{name: fields}`,
+///   `Option` is decoded into `null` or the inner value.
+/// - the bytes are decoded into hex string, and the integers wider than 64 bits into string.
+pub fn decode_value(
+    types: &PortableRegistry,
+    ty: u32,
+    input: &mut &[u8],
+) -> Result<Value, DecodeError> {
+    let resolved = types.resolve(ty).ok_or(DecodeError::TypeNotFound(ty))?;
+    match resolved.type_def() {
+        TypeDef::Composite(composite) => decode_fields(types, composite.fields(), input),
+        TypeDef::Variant(_) => {
+            let variant = read_variant(types, ty, input)?;
+            let fields = decode_fields(types, variant.fields(), input)?;
+            if matches!(resolved.path().segments(), [name] if name == "Option") {
+                return Ok(fields);
+            }
+            if variant.fields().is_empty() {
+                Ok(Value::String(variant.name().clone()))
+            } else {
+                let mut map = Map::new();
+                map.insert(variant.name().clone(), fields);
+                Ok(Value::Object(map))
+            }
+        }
+        TypeDef::Sequence(sequence) => {
+            let elem = sequence.type_param().id();
+            if is_u8(types, elem) {
+                return Ok(to_hex(&Vec::<u8>::decode(input)?));
+            }
+            let len = Compact::<u32>::decode(input)?.0;
+            let mut values = Vec::new();
+            for _ in 0..len {
+                values.push(decode_value(types, elem, input)?);
+            }
+            Ok(Value::Array(values))
+        }
+        TypeDef::Array(array) => {
+            let elem = array.type_param().id();
+            if is_u8(types, elem) {
+                return Ok(to_hex(&read_bytes(input, array.len() as usize)?));
+            }
+            let mut values = Vec::new();
+            for _ in 0..array.len() {
+                values.push(decode_value(types, elem, input)?);
+            }
+            Ok(Value::Array(values))
+        }
+        TypeDef::Tuple(tuple) => {
+            if tuple.fields().is_empty() {
+                return Ok(Value::Null);
+            }
+            let mut values = Vec::with_capacity(tuple.fields().len());
+            for field in tuple.fields() {
+                values.push(decode_value(types, field.id(), input)?);
+            }
+            Ok(Value::Array(values))
+        }
+        TypeDef::Primitive(primitive) => decode_primitive(primitive, input),
+        TypeDef::Compact(compact) => decode_compact(types, compact.type_param().id(), input),
+        TypeDef::BitSequence(bits) => {
+            let store_ty = bits.bit_store_type().id();
+            let store_size = match types.resolve(store_ty).map(|ty| ty.type_def()) {
+                Some(TypeDef::Primitive(TypeDefPrimitive::U8)) => 1,
+                Some(TypeDef::Primitive(TypeDefPrimitive::U16)) => 2,
+                Some(TypeDef::Primitive(TypeDefPrimitive::U32)) => 4,
+                Some(TypeDef::Primitive(TypeDefPrimitive::U64)) => 8,
+                _ => return Err(DecodeError::UnexpectedType(store_ty, "bit store")),
+            };
+            // the bits are stored in the minimum number of store elements.
+            let bits = Compact::<u32>::decode(input)?.0 as usize;
+            let len = (bits + store_size * 8 - 1) / (store_size * 8) * store_size;
+            Ok(to_hex(&read_bytes(input, len)?))
+        }
+    }
+}
+
+/// Decodes the fields of a struct or an enum variant.
+pub(super) fn decode_fields(
+    types: &PortableRegistry,
+    fields: &[Field<PortableForm>],
+    input: &mut &[u8],
+) -> Result<Value, DecodeError> {
+    match fields {
+        [] => Ok(Value::Null),
+        [field] if field.name().is_none() => decode_value(types, field.ty().id(), input),
+        _ if fields.iter().all(|field| field.name().is_some()) => {
+            let mut map = Map::new();
+            for field in fields {
+                let value = decode_value(types, field.ty().id(), input)?;
+                map.insert(field.name().cloned().unwrap_or_default(), value);
+            }
+            Ok(Value::Object(map))
+        }
+        _ => {
+            let mut values = Vec::with_capacity(fields.len());
+            for field in fields {
+                values.push(decode_value(types, field.ty().id(), input)?);
+            }
+            Ok(Value::Array(values))
+        }
+    }
+}
+
+/// Reads the index of the enum type `ty`, and returns the variant of the index.
+pub(super) fn read_variant<'a>(
+    types: &'a PortableRegistry,
+    ty: u32,
+    input: &mut &[u8],
+) -> Result<&'a Variant<PortableForm>, DecodeError> {
+    let variants = match types.resolve(ty).map(|resolved| resolved.type_def()) {
+        Some(TypeDef::Variant(variant)) => variant.variants(),
+        Some(_) => return Err(DecodeError::UnexpectedType(ty, "enum")),
+        None => return Err(DecodeError::TypeNotFound(ty)),
+    };
+    let index = u8::decode(input)?;
+    variants
+        .iter()
+        .find(|variant| variant.index() == index)
+        .ok_or(DecodeError::VariantNotFound(ty, index))
+}
+
+fn decode_primitive(primitive: &TypeDefPrimitive, input: &mut &[u8]) -> Result<Value, DecodeError> {
+    Ok(match primitive {
+        TypeDefPrimitive::Bool => bool::decode(input)?.into(),
+        TypeDefPrimitive::Char => {
+            let c = char::from_u32(u32::decode(input)?)
+                .ok_or_else(|| codec::Error::from("Invalid char"))?;
+            Value::String(c.to_string())
+        }
+        TypeDefPrimitive::Str => String::decode(input)?.into(),
+        TypeDefPrimitive::U8 => u8::decode(input)?.into(),
+        TypeDefPrimitive::U16 => u16::decode(input)?.into(),
+        TypeDefPrimitive::U32 => u32::decode(input)?.into(),
+        TypeDefPrimitive::U64 => u64::decode(input)?.into(),
+        TypeDefPrimitive::U128 => u128::decode(input)?.to_string().into(),
+        TypeDefPrimitive::I8 => i8::decode(input)?.into(),
+        TypeDefPrimitive::I16 => i16::decode(input)?.into(),
+        TypeDefPrimitive::I32 => i32::decode(input)?.into(),
+        TypeDefPrimitive::I64 => i64::decode(input)?.into(),
+        TypeDefPrimitive::I128 => i128::decode(input)?.to_string().into(),
+        TypeDefPrimitive::U256 | TypeDefPrimitive::I256 => to_hex(&read_bytes(input, 32)?),
+    })
+}
+
+fn decode_compact(
+    types: &PortableRegistry,
+    ty: u32,
+    input: &mut &[u8],
+) -> Result<Value, DecodeError> {
+    match types.resolve(ty).map(|resolved| resolved.type_def()) {
+        Some(TypeDef::Primitive(primitive)) => Ok(match primitive {
+            TypeDefPrimitive::U8 => Compact::<u8>::decode(input)?.0.into(),
+            TypeDefPrimitive::U16 => Compact::<u16>::decode(input)?.0.into(),
+            TypeDefPrimitive::U32 => Compact::<u32>::decode(input)?.0.into(),
+            TypeDefPrimitive::U64 => Compact::<u64>::decode(input)?.0.into(),
+            TypeDefPrimitive::U128 => Compact::<u128>::decode(input)?.0.to_string().into(),
+            _ => return Err(DecodeError::UnexpectedType(ty, "compact integer")),
+        }),
+        // e.g. `Compact<Perbill>`, the struct with only one field is compact encoded as the field.
+        Some(TypeDef::Composite(composite)) if composite.fields().len() == 1 => {
+            let field = &composite.fields()[0];
+            let value = decode_compact(types, field.ty().id(), input)?;
+            Ok(match field.name() {
+                Some(name) => {
+                    let mut map = Map::new();
+                    map.insert(name.clone(), value);
+                    Value::Object(map)
+                }
+                None => value,
+            })
+        }
+        Some(TypeDef::Tuple(tuple)) if tuple.fields().is_empty() => Ok(Value::Null),
+        Some(_) => Err(DecodeError::UnexpectedType(ty, "compact integer")),
+        None => Err(DecodeError::TypeNotFound(ty)),
+    }
+}
+
+fn is_u8(types: &PortableRegistry, ty: u32) -> bool {
+    matches!(
+        types.resolve(ty).map(|resolved| resolved.type_def()),
+        Some(TypeDef::Primitive(TypeDefPrimitive::U8))
+    )
+}
+
+fn read_bytes(input: &mut &[u8], len: usize) -> Result<Vec<u8>, DecodeError> {
+    if input.len() < len {
+        return Err(codec::Error::from("Not enough data to fill buffer").into());
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes.to_vec())
+}
+
+pub(super) fn to_hex(bytes: &[u8]) -> Value {
+    Value::String(format!("0x{}", hex::encode(bytes)))
+}
+
+/// Converts the hex string produced by `to_hex` back into bytes.
+pub(super) fn from_hex(value: &Value) -> Option<Vec<u8>> {
+    let hex = value.as_str()?.strip_prefix("0x")?;
+    hex::decode(hex).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+    use scale_info::{meta_type, Registry};
+
+    #[test]
+    fn decode_std_types() {
+        type Ty = (u32, Vec<u8>, bool, Option<u64>, Compact<u128>, [u16; 2]);
+        let mut registry = Registry::new();
+        let ty = registry.register_type(&meta_type::<Ty>()).id();
+        let types = PortableRegistry::from(registry);
+
+        let value: Ty = (1, vec![1, 2], true, None, Compact(1000), [3, 4]);
+        let encoded = value.encode();
+        let input = &mut &encoded[..];
+        assert_eq!(
+            decode_value(&types, ty, input).unwrap(),
+            serde_json::json!([1, "0x0102", true, null, "1000", [3, 4]])
+        );
+        assert!(input.is_empty());
+    }
+}
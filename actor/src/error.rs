@@ -15,6 +15,19 @@ pub enum ActorError {
     Postgres(#[from] archive_postgres::SqlxError),
     #[error("{0}")]
     Kafka(#[from] archive_kafka::KafkaError),
+    #[error("{0}")]
+    Codec(#[from] codec::Error),
+
+    #[error("Block #{0} is missing")]
+    MissingBlock(u32),
+    #[error("Metadata of version {0} is missing")]
+    MissingMetadata(u32),
+    #[error("`:code` of Block #{0} is missing")]
+    MissingCode(u32),
+    #[error("Block #{0} is inconsistent with the canonical chain of the node")]
+    InconsistentBlock(u32),
+    #[error("Actors have been restarted {0} times, give up")]
+    TooManyRestarts(u32),
 }
 
 impl From<sp_api::ApiError> for ActorError {
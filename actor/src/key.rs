@@ -0,0 +1,156 @@
+use sp_blockchain::well_known_cache_keys;
+use sp_storage::well_known_keys;
+
+/// The kind of a main storage key.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StorageKeyKind {
+    /// The well known keys, e.g. `:code`, `:heappages`.
+    WellKnown,
+    /// `Twox128(pallet prefix) ++ Twox128(storage entry name)`.
+    PalletValue,
+    /// `Twox128(pallet prefix) ++ Twox128(storage entry name) ++ hashed keys`.
+    PalletMap,
+    /// `:child_storage:` prefixed key, whose value is the root of the child trie.
+    ChildTrieRoot,
+    /// Any other key, e.g. the custom `:` keys or the keys shorter than 32 bytes.
+    Unknown,
+}
+
+impl StorageKeyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::WellKnown => "well_known",
+            Self::PalletValue => "pallet_value",
+            Self::PalletMap => "pallet_map",
+            Self::ChildTrieRoot => "child_trie_root",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+// Twox128(pallet prefix) ++ Twox128(storage entry name) = 32 bytes
+// e.g: prefix = 0b76934f4cc08dee01012d059e1b83ee5e0621c4869aa60c02be9adcc98a0d1d
+const PALLET_PREFIX_LEN: usize = 32;
+
+// The keys starting with `:` are reserved for the special keys, they are never pallet keys.
+const SPECIAL_KEY_PREFIX: u8 = b':';
+
+/// Classifies the main storage key, returns the kind and the prefix of key.
+pub fn classify_key(key: &[u8]) -> (StorageKeyKind, &[u8]) {
+    if is_well_known_key(key) {
+        (StorageKeyKind::WellKnown, key)
+    } else if key.starts_with(well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX) {
+        (
+            StorageKeyKind::ChildTrieRoot,
+            well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX,
+        )
+    } else if key.starts_with(well_known_keys::CHILD_STORAGE_KEY_PREFIX) {
+        (
+            StorageKeyKind::ChildTrieRoot,
+            well_known_keys::CHILD_STORAGE_KEY_PREFIX,
+        )
+    } else if key.first() == Some(&SPECIAL_KEY_PREFIX) {
+        (StorageKeyKind::Unknown, key)
+    } else if key.len() == PALLET_PREFIX_LEN {
+        (StorageKeyKind::PalletValue, key)
+    } else if key.len() > PALLET_PREFIX_LEN {
+        (StorageKeyKind::PalletMap, &key[..PALLET_PREFIX_LEN])
+    } else {
+        (StorageKeyKind::Unknown, key)
+    }
+}
+
+fn is_well_known_key(key: &[u8]) -> bool {
+    const CACHE_KEY_AUTH: &[u8] = &well_known_cache_keys::AUTHORITIES;
+    const CACHE_KEY_EPOCH: &[u8] = &well_known_cache_keys::EPOCH;
+    const CACHE_KEY_CHANGES_TRIE_CONFIG: &[u8] = &well_known_cache_keys::CHANGES_TRIE_CONFIG;
+    matches!(
+        key,
+        well_known_keys::CODE
+            | well_known_keys::HEAP_PAGES
+            | well_known_keys::EXTRINSIC_INDEX
+            | well_known_keys::CHILD_STORAGE_KEY_PREFIX
+            | well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX
+            | CACHE_KEY_AUTH
+            | CACHE_KEY_EPOCH
+            | CACHE_KEY_CHANGES_TRIE_CONFIG
+            | sp_finality_grandpa::GRANDPA_AUTHORITIES_KEY
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_keys() {
+        assert_eq!(
+            classify_key(well_known_keys::CODE),
+            (StorageKeyKind::WellKnown, well_known_keys::CODE)
+        );
+        let child = [well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX, b"id"].concat();
+        assert_eq!(
+            classify_key(&child),
+            (
+                StorageKeyKind::ChildTrieRoot,
+                well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX
+            )
+        );
+        let value = [1u8; 32];
+        assert_eq!(
+            classify_key(&value),
+            (StorageKeyKind::PalletValue, &value[..])
+        );
+        let map = [1u8; 40];
+        assert_eq!(classify_key(&map), (StorageKeyKind::PalletMap, &map[..32]));
+        assert_eq!(
+            classify_key(b":custom"),
+            (StorageKeyKind::Unknown, &b":custom"[..])
+        );
+        assert_eq!(classify_key(&[]), (StorageKeyKind::Unknown, &[][..]));
+    }
+
+    #[test]
+    fn classify_special_keys() {
+        // the custom `:` keys are never pallet keys, whatever their lengths are.
+        let custom = [&b":custom"[..], &[1u8; 40]].concat();
+        assert_eq!(
+            classify_key(&custom),
+            (StorageKeyKind::Unknown, &custom[..])
+        );
+        let custom = [&b":"[..], &[1u8; 31]].concat();
+        assert_eq!(
+            classify_key(&custom),
+            (StorageKeyKind::Unknown, &custom[..])
+        );
+
+        // the child storage keys shorter and longer than 32 bytes.
+        for len in [1, 32] {
+            let child = [
+                well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX,
+                &vec![1u8; len][..],
+            ]
+            .concat();
+            assert_eq!(
+                classify_key(&child),
+                (
+                    StorageKeyKind::ChildTrieRoot,
+                    well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX
+                )
+            );
+            let child = [
+                well_known_keys::CHILD_STORAGE_KEY_PREFIX,
+                &b"custom:"[..],
+                &vec![1u8; len][..],
+            ]
+            .concat();
+            assert_eq!(
+                classify_key(&child),
+                (
+                    StorageKeyKind::ChildTrieRoot,
+                    well_known_keys::CHILD_STORAGE_KEY_PREFIX
+                )
+            );
+        }
+    }
+}
@@ -1,11 +1,19 @@
 mod actors;
 mod config;
+mod decode;
 mod error;
 mod exec;
+mod key;
 mod message;
+mod supervisor;
 
 pub use self::{
     actors::Actors,
-    config::{ActorConfig, DispatcherConfig, KafkaConfig, PostgresConfig, SchedulerConfig},
+    config::{
+        ActorConfig, AdaptiveConfig, BackfillConfig, DispatcherConfig, FollowMode, KafkaConfig,
+        MailboxConfig, PostgresConfig, RelayConfig, RepairConfig, RetryConfig, SchedulerConfig,
+        SupervisorConfig, SyncMode,
+    },
     error::ActorError,
+    supervisor::Supervisor,
 };
@@ -1,7 +1,6 @@
 use std::{collections::HashMap, marker::PhantomData};
 
-use codec::Encode;
-use sp_blockchain::well_known_cache_keys;
+use codec::{Decode, Encode};
 use sp_runtime::{
     generic::SignedBlock,
     traits::{Block as BlockT, Header as HeaderT},
@@ -9,8 +8,12 @@ use sp_runtime::{
 };
 use sp_state_machine::{ChildStorageCollection, StorageCollection};
 use sp_storage::{well_known_keys, StorageData, StorageKey};
+use sp_version::RuntimeVersion;
 
-use crate::error::{ActorError, SqlxError};
+use crate::{
+    error::{ActorError, SqlxError},
+    key::classify_key,
+};
 
 // ============================================================================
 // `Data` Actor Message
@@ -56,28 +59,66 @@ pub struct BlockMessage<Block: BlockT> {
     pub inner: SignedBlock<Block>,
     pub main_changes: StorageCollection,
     pub child_changes: ChildStorageCollection,
-}
+    pub runtime: RuntimeVersion,
+    // The hash of `:code` at the block.
+    pub code_hash: Vec<u8>,
+    // `:code` at the block, only set when it's changed or unknown by the sender.
+    pub code: Option<Vec<u8>>,
+}
+
+/// Returns the new `:code` if it's changed by the storage changes.
+pub fn changed_code(main_changes: &StorageCollection) -> Option<&[u8]> {
+    main_changes.iter().find_map(|(key, value)| match value {
+        Some(code) if key.as_slice() == well_known_keys::CODE => Some(code.as_slice()),
+        _ => None,
+    })
+}
+
+impl<Block: BlockT> BlockMessage<Block> {
+    /// Returns the approximate size of the message in memory.
+    pub fn size_hint(&self) -> usize {
+        fn changes_size(changes: &StorageCollection) -> usize {
+            changes
+                .iter()
+                .map(|(key, value)| key.len() + value.as_ref().map_or(0, Vec::len))
+                .sum()
+        }
 
-impl<Block: BlockT> xtra::Message for BlockMessage<Block> {
-    type Result = ();
+        self.inner.encoded_size()
+            + self.code.as_ref().map_or(0, Vec::len)
+            + changes_size(&self.main_changes)
+            + self
+                .child_changes
+                .iter()
+                .map(|(key, changes)| key.len() + changes_size(changes))
+                .sum::<usize>()
+    }
+
+    /// Returns the runtime version model which is only active at this block.
+    pub fn runtime_version_model(&self) -> archive_postgres::RuntimeVersionModel {
+        let block_num = (*self.inner.block.header().number()).saturated_into();
+        archive_postgres::RuntimeVersionModel {
+            code_hash: self.code_hash.clone(),
+            spec_name: self.runtime.spec_name.to_string(),
+            impl_name: self.runtime.impl_name.to_string(),
+            authoring_version: self.runtime.authoring_version,
+            spec_version: self.runtime.spec_version,
+            impl_version: self.runtime.impl_version,
+            transaction_version: self.runtime.transaction_version,
+            apis: self
+                .runtime
+                .apis
+                .iter()
+                .map(|(id, version)| serde_json::json!([format!("0x{}", hex::encode(id)), version]))
+                .collect(),
+            first_block: block_num,
+            last_block: block_num,
+        }
+    }
 }
 
-fn is_well_known_key(key: &[u8]) -> bool {
-    const CACHE_KEY_AUTH: &[u8] = &well_known_cache_keys::AUTHORITIES;
-    const CACHE_KEY_EPOCH: &[u8] = &well_known_cache_keys::EPOCH;
-    const CACHE_KEY_CHANGES_TRIE_CONFIG: &[u8] = &well_known_cache_keys::CHANGES_TRIE_CONFIG;
-    matches!(
-        key,
-        well_known_keys::CODE
-            | well_known_keys::HEAP_PAGES
-            | well_known_keys::EXTRINSIC_INDEX
-            | well_known_keys::CHILD_STORAGE_KEY_PREFIX
-            | well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX
-            | CACHE_KEY_AUTH
-            | CACHE_KEY_EPOCH
-            | CACHE_KEY_CHANGES_TRIE_CONFIG
-            | sp_finality_grandpa::GRANDPA_AUTHORITIES_KEY
-    )
+impl<Block: BlockT> xtra::Message for BlockMessage<Block> {
+    type Result = Result<(), ActorError>;
 }
 
 fn into_block_model<Block: BlockT>(
@@ -117,24 +158,14 @@ fn into_main_storage_models(
     main_changes
         .into_iter()
         .map(|(key, data)| {
-            if is_well_known_key(&key) {
-                archive_postgres::MainStorageChangeModel {
-                    block_num,
-                    block_hash: block_hash.clone(),
-                    prefix: key.clone(),
-                    key,
-                    data,
-                }
-            } else {
-                archive_postgres::MainStorageChangeModel {
-                    block_num,
-                    block_hash: block_hash.clone(),
-                    // Twox128(module_prefix) ++ Twox128(storage_prefix) = 32 bytes
-                    // e.g: prefix = 0b76934f4cc08dee01012d059e1b83ee5e0621c4869aa60c02be9adcc98a0d1d
-                    prefix: key.as_slice()[..32].to_vec(),
-                    key,
-                    data,
-                }
+            let (kind, prefix) = classify_key(&key);
+            archive_postgres::MainStorageChangeModel {
+                block_num,
+                block_hash: block_hash.clone(),
+                prefix: prefix.to_vec(),
+                kind: kind.as_str().to_string(),
+                key,
+                data,
             }
         })
         .collect()
@@ -233,7 +264,7 @@ impl<Block: BlockT> BatchBlockMessage<Block> {
 }
 
 impl<Block: BlockT> xtra::Message for BatchBlockMessage<Block> {
-    type Result = ();
+    type Result = Result<(), ActorError>;
 }
 
 impl<Block: BlockT> From<BatchBlockMessage<Block>>
@@ -278,7 +309,7 @@ pub struct BestBlockMessage<Block: BlockT> {
 }
 
 impl<Block: BlockT> xtra::Message for BestBlockMessage<Block> {
-    type Result = ();
+    type Result = Result<(), ActorError>;
 }
 
 impl<Block: BlockT> From<BestBlockMessage<Block>> for archive_postgres::BestBlockModel {
@@ -299,6 +330,27 @@ impl<Block: BlockT> From<BestBlockMessage<Block>> for archive_kafka::BestBlockPa
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct FailedBlockMessage {
+    pub block_num: u32,
+    pub attempts: u32,
+    pub error: String,
+}
+
+impl xtra::Message for FailedBlockMessage {
+    type Result = Result<(), SqlxError>;
+}
+
+impl From<FailedBlockMessage> for archive_postgres::FailedBlockModel {
+    fn from(failed_block: FailedBlockMessage) -> Self {
+        Self {
+            block_num: failed_block.block_num,
+            attempts: failed_block.attempts,
+            error: failed_block.error,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct FinalizedBlockMessage<Block: BlockT> {
     pub block_num: <Block::Header as HeaderT>::Number,
@@ -307,7 +359,7 @@ pub struct FinalizedBlockMessage<Block: BlockT> {
 }
 
 impl<Block: BlockT> xtra::Message for FinalizedBlockMessage<Block> {
-    type Result = ();
+    type Result = Result<(), ActorError>;
 }
 
 impl<Block: BlockT> From<FinalizedBlockMessage<Block>> for archive_postgres::FinalizedBlockModel {
@@ -331,6 +383,77 @@ impl<Block: BlockT> From<FinalizedBlockMessage<Block>>
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct RollbackMessage<Block: BlockT> {
+    pub old_block_num: <Block::Header as HeaderT>::Number,
+    pub old_block_hash: Block::Hash,
+    pub new_block_num: <Block::Header as HeaderT>::Number,
+    // `None` if the new tip has not been archived.
+    pub new_block_hash: Option<Block::Hash>,
+    // The hashes of the retracted blocks, in descending order of the number.
+    pub retracted: Vec<Block::Hash>,
+}
+
+impl<Block: BlockT> RollbackMessage<Block> {
+    /// Returns `None` if no archived block is retracted,
+    /// the `orphaned` blocks are in descending order of the number.
+    pub fn new(
+        new_block_num: u32,
+        new_block_hash: Option<Vec<u8>>,
+        orphaned: Vec<(u32, Vec<u8>)>,
+    ) -> Result<Option<Self>, codec::Error> {
+        let (old_block_num, old_block_hash) = match orphaned.first() {
+            Some((block_num, block_hash)) => (*block_num, decode_hash::<Block>(block_hash)?),
+            None => return Ok(None),
+        };
+        Ok(Some(Self {
+            old_block_num: old_block_num.into(),
+            old_block_hash,
+            new_block_num: new_block_num.into(),
+            new_block_hash: new_block_hash
+                .map(|hash| decode_hash::<Block>(&hash))
+                .transpose()?,
+            retracted: orphaned
+                .iter()
+                .map(|(_, hash)| decode_hash::<Block>(hash))
+                .collect::<Result<_, _>>()?,
+        }))
+    }
+}
+
+// The stored hash must be exactly one block hash, the trailing bytes are rejected.
+fn decode_hash<Block: BlockT>(hash: &[u8]) -> Result<Block::Hash, codec::Error> {
+    let mut input = hash;
+    let decoded = Block::Hash::decode(&mut input)?;
+    if !input.is_empty() {
+        return Err("Block hash has trailing bytes".into());
+    }
+    Ok(decoded)
+}
+
+impl<Block: BlockT> From<RollbackMessage<Block>> for archive_kafka::RollbackPayload<Block> {
+    fn from(rollback: RollbackMessage<Block>) -> Self {
+        Self {
+            old_block_num: rollback.old_block_num,
+            old_block_hash: rollback.old_block_hash,
+            new_block_num: rollback.new_block_num,
+            new_block_hash: rollback.new_block_hash,
+            retracted: rollback.retracted,
+        }
+    }
+}
+
+/// The outbox record of `id` to be published by the sink.
+#[derive(Clone, Debug)]
+pub struct PublishMessage {
+    pub id: i64,
+    pub record: archive_kafka::PayloadRecord,
+}
+
+impl xtra::Message for PublishMessage {
+    type Result = Result<(), ActorError>;
+}
+
 // ============================================================================
 // `Communication` Actor Message
 // ============================================================================
@@ -362,16 +485,74 @@ impl xtra::Message for DbFinalizedBlock {
 }
 
 #[derive(Copy, Clone, Debug)]
-pub struct DbDeleteGtBlockNum {
+pub struct DbOrphanGtBlockNum {
+    pub block_num: u32,
+}
+impl DbOrphanGtBlockNum {
+    pub fn new(block_num: u32) -> Self {
+        Self { block_num }
+    }
+}
+impl xtra::Message for DbOrphanGtBlockNum {
+    // the number of orphaned blocks
+    type Result = Result<u64, ActorError>;
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct DbEnsurePartitions {
     pub block_num: u32,
 }
-impl DbDeleteGtBlockNum {
+impl DbEnsurePartitions {
     pub fn new(block_num: u32) -> Self {
         Self { block_num }
     }
 }
-impl xtra::Message for DbDeleteGtBlockNum {
-    type Result = Result<u64, SqlxError>;
+impl xtra::Message for DbEnsurePartitions {
+    type Result = Result<(), SqlxError>;
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct DbBlockHashes {
+    pub from: u32,
+    pub to: u32,
+}
+impl DbBlockHashes {
+    pub fn new(from: u32, to: u32) -> Self {
+        Self { from, to }
+    }
+}
+impl xtra::Message for DbBlockHashes {
+    // (block_num, block_hash) in descending order of block_num
+    type Result = Result<Vec<(u32, Vec<u8>)>, SqlxError>;
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct DbBlockGaps {
+    pub from: u32,
+    pub to: u32,
+}
+impl DbBlockGaps {
+    pub fn new(from: u32, to: u32) -> Self {
+        Self { from, to }
+    }
+}
+impl xtra::Message for DbBlockGaps {
+    // the ranges [start, end] of the missing blocks
+    type Result = Result<Vec<(u32, u32)>, SqlxError>;
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct DbBlocksWithoutMainStorage {
+    pub from: u32,
+    pub to: u32,
+}
+impl DbBlocksWithoutMainStorage {
+    pub fn new(from: u32, to: u32) -> Self {
+        Self { from, to }
+    }
+}
+impl xtra::Message for DbBlocksWithoutMainStorage {
+    type Result = Result<Vec<u32>, SqlxError>;
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -403,6 +584,46 @@ impl xtra::Message for Tick {
     type Result = Result<(), ActorError>;
 }
 
+#[derive(Copy, Clone, Debug)]
+pub struct Backfill {
+    pub from: u32,
+    pub to: u32,
+}
+impl Backfill {
+    pub fn new(from: u32, to: u32) -> Self {
+        Self { from, to }
+    }
+}
+impl xtra::Message for Backfill {
+    // the last block number that has been archived
+    type Result = Result<u32, ActorError>;
+}
+
+/// Re-crawl the blocks within the range `[from, to]` that are missing or have no main storage,
+/// `from` defaults to the block after the last checked one,
+/// `to` defaults to the latest archived finalized block.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Repair {
+    pub from: Option<u32>,
+    pub to: Option<u32>,
+}
+impl Repair {
+    pub fn new(from: Option<u32>, to: Option<u32>) -> Self {
+        Self { from, to }
+    }
+}
+impl xtra::Message for Repair {
+    // the number of the re-crawled blocks
+    type Result = Result<u32, ActorError>;
+}
+
+/// Publish the outbox records after the committed offset of the sink.
+#[derive(Copy, Clone, Debug)]
+pub struct Relay;
+impl xtra::Message for Relay {
+    type Result = Result<(), ActorError>;
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct CrawlBestAndFinalized;
 impl xtra::Message for CrawlBestAndFinalized {
@@ -421,7 +642,15 @@ impl<Block: BlockT> CrawlBlock<Block> {
     }
 }
 impl<Block: BlockT> xtra::Message for CrawlBlock<Block> {
-    type Result = Option<BlockMessage<Block>>;
+    // `None` means that the block doesn't exist yet
+    type Result = Result<Option<BlockMessage<Block>>, ActorError>;
+}
+
+/// The mailboxes are FIFO, so `Flush` is handled after all the messages sent before it.
+#[derive(Copy, Clone, Debug)]
+pub struct Flush;
+impl xtra::Message for Flush {
+    type Result = ();
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -429,3 +658,58 @@ pub struct Die;
 impl xtra::Message for Die {
     type Result = ();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::H256;
+    use sp_runtime::testing::{Block as TestBlock, ExtrinsicWrapper};
+
+    type Block = TestBlock<ExtrinsicWrapper<u64>>;
+
+    #[test]
+    fn rollback_of_orphaned_blocks() {
+        let orphaned = vec![(12, vec![12; 32]), (11, vec![11; 32])];
+        let rollback = RollbackMessage::<Block>::new(10, Some(vec![10; 32]), orphaned)
+            .unwrap()
+            .unwrap();
+        assert_eq!(rollback.old_block_num, 12);
+        assert_eq!(rollback.old_block_hash, H256::repeat_byte(12));
+        assert_eq!(rollback.new_block_num, 10);
+        assert_eq!(rollback.new_block_hash, Some(H256::repeat_byte(10)));
+        assert_eq!(
+            rollback.retracted,
+            vec![H256::repeat_byte(12), H256::repeat_byte(11)]
+        );
+
+        // the new tip has not been archived.
+        let rollback = RollbackMessage::<Block>::new(10, None, vec![(11, vec![11; 32])])
+            .unwrap()
+            .unwrap();
+        assert_eq!(rollback.new_block_hash, None);
+        assert_eq!(rollback.retracted, vec![H256::repeat_byte(11)]);
+    }
+
+    #[test]
+    fn rollback_without_orphaned_blocks() {
+        assert!(
+            RollbackMessage::<Block>::new(10, Some(vec![10; 32]), vec![])
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn rollback_of_invalid_hashes() {
+        assert!(RollbackMessage::<Block>::new(10, None, vec![(11, vec![11; 31])]).is_err());
+        assert!(
+            RollbackMessage::<Block>::new(10, Some(vec![10; 31]), vec![(11, vec![11; 32])])
+                .is_err()
+        );
+        assert!(RollbackMessage::<Block>::new(10, None, vec![(11, vec![11; 33])]).is_err());
+        assert!(
+            RollbackMessage::<Block>::new(10, Some(vec![10; 33]), vec![(11, vec![11; 32])])
+                .is_err()
+        );
+    }
+}
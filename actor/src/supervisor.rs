@@ -0,0 +1,89 @@
+use std::{future::Future, marker::PhantomData, sync::Arc, time::Duration};
+
+use futures::future::{self, Either};
+
+use sc_client_api::{
+    backend::{self, StateBackendFor},
+    client::BlockBackend,
+};
+use sp_api::{ApiExt, Core as CoreApi, Metadata as MetadataApi, ProvideRuntimeApi};
+use sp_runtime::traits::Block as BlockT;
+
+use crate::{actors::Actors, config::ActorConfig, error::ActorError};
+
+/// Supervise the actors that follow the chain, all of them are restarted when any of them
+/// stops unexpectedly, and the scheduler resumes from the last block stored in postgres.
+pub struct Supervisor<Block, Backend, Api> {
+    backend: Arc<Backend>,
+    api: Arc<Api>,
+    config: ActorConfig,
+    restarts: u32,
+    _marker: PhantomData<Block>,
+}
+
+impl<Block, Backend, Api> Supervisor<Block, Backend, Api>
+where
+    Block: BlockT,
+    Backend: backend::Backend<Block> + BlockBackend<Block> + 'static,
+    Api: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+    <Api as ProvideRuntimeApi<Block>>::Api: CoreApi<Block>
+        + MetadataApi<Block>
+        + ApiExt<Block, StateBackend = StateBackendFor<Backend, Block>>,
+{
+    pub fn new(backend: Arc<Backend>, api: Arc<Api>, config: ActorConfig) -> Self {
+        Self {
+            backend,
+            api,
+            config,
+            restarts: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    async fn spawn(&self) -> Result<Actors<Block, Backend, Api>, ActorError> {
+        let actors =
+            Actors::spawn(self.backend.clone(), self.api.clone(), self.config.clone()).await?;
+        actors.tick_interval().await?;
+        Ok(actors)
+    }
+
+    /// Run the actors until `shutdown` is resolved, returns error if the actors have been
+    /// restarted more than `max_restarts` times.
+    pub async fn run(mut self, shutdown: impl Future<Output = ()>) -> Result<(), ActorError> {
+        let interval = Duration::from_millis(self.config.supervisor.interval_ms);
+        let drain_timeout = Duration::from_millis(self.config.supervisor.drain_timeout_ms);
+        let mut actors = self.spawn().await?;
+        let mut shutdown = Box::pin(shutdown);
+        loop {
+            match future::select(Box::pin(tokio::time::sleep(interval)), shutdown).await {
+                Either::Left((_, unresolved)) => shutdown = unresolved,
+                Either::Right(_) => break,
+            }
+
+            let stopped = actors.stopped_actors();
+            if stopped.is_empty() {
+                continue;
+            }
+            log::error!(target: "actor", "Actors stopped unexpectedly: {:?}", stopped);
+            actors.kill(drain_timeout).await?;
+            if self.restarts >= self.config.supervisor.max_restarts {
+                return Err(ActorError::TooManyRestarts(self.restarts));
+            }
+            self.restarts += 1;
+            // resume from the last block stored in postgres instead of the configured one.
+            self.config.scheduler.start_block = None;
+            log::warn!(
+                target: "actor",
+                "Restart actors ({}/{})",
+                self.restarts, self.config.supervisor.max_restarts
+            );
+            actors = self.spawn().await?;
+        }
+
+        actors.drain(drain_timeout).await;
+        log::info!(target: "actor", "Stopping All Actors");
+        actors.kill(drain_timeout).await?;
+        log::info!(target: "actor", "Stopped All Actors");
+        Ok(())
+    }
+}
@@ -1,8 +1,3 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
-};
-
 use serde::{Deserialize, Serialize};
 
 use sc_chain_spec::{ChainSpecExtension, GenericChainSpec};
@@ -53,15 +48,11 @@ fn main() -> Result<(), ArchiveError> {
     let archive = PolkadotArchiveSystemBuilder::with_config(config).build(&chain_spec)?;
     archive.drive()?;
 
-    let running = Arc::new(AtomicBool::new(true));
-    let r = running.clone();
-    ctrlc::set_handler(move || {
-        r.store(false, Ordering::SeqCst);
-    })
-    .expect("Error setting Ctrl-C handler");
+    let kill = archive.kill_handle();
+    ctrlc::set_handler(move || kill.kill()).expect("Error setting Ctrl-C handler");
 
-    while running.load(Ordering::SeqCst) {}
-    archive.shutdown()?;
+    // blocks until the archive finishes by itself or is killed by Ctrl-C.
+    archive.wait()?;
 
     Ok(())
 }
@@ -13,7 +13,9 @@ async fn main() -> Result<(), KafkaError> {
         topic: KafkaTopicConfig {
             metadata: "polkadot-metadata-dev".into(),
             block: "polkadot-block-dev".into(),
+            best_block: "polkadot-best-block-dev".into(),
             finalized_block: "polkadot-finalized-block-dev".into(),
+            rollback: "polkadot-rollback-dev".into(),
         },
         rdkafka: {
             let mut rdkakfa = HashMap::new();
@@ -56,6 +58,12 @@ async fn main() -> Result<(), KafkaError> {
         };
         producer.send(block).await?;
 
+        let best_block = BestBlockPayloadDemo {
+            block_num: i as u32,
+            block_hash: "0x00".into(),
+        };
+        producer.send(best_block).await?;
+
         let finalized_block = FinalizedBlockPayloadDemo {
             block_num: i as u32,
             block_hash: "0x00".into(),
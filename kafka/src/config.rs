@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
+use crate::payload::PayloadKind;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct KafkaConfig {
     pub queue_timeout: u64, // seconds
@@ -13,5 +15,20 @@ pub struct KafkaConfig {
 pub struct KafkaTopicConfig {
     pub metadata: String,
     pub block: String,
+    pub best_block: String,
     pub finalized_block: String,
+    pub rollback: String,
+}
+
+impl KafkaTopicConfig {
+    /// Returns the topic that the kind of payload is published to.
+    pub fn topic(&self, kind: PayloadKind) -> &str {
+        match kind {
+            PayloadKind::Metadata => &self.metadata,
+            PayloadKind::Block => &self.block,
+            PayloadKind::BestBlock => &self.best_block,
+            PayloadKind::FinalizedBlock => &self.finalized_block,
+            PayloadKind::Rollback => &self.rollback,
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, str::FromStr};
 
 use serde::{Serialize, Serializer};
 
@@ -61,6 +61,132 @@ pub struct FinalizedBlockPayload<Block: BlockT> {
     pub timestamp: i64,
 }
 
+/// The archived blocks after the new tip are retracted by the rollback,
+/// the consumers should undo the data derived from the retracted blocks.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RollbackPayload<Block: BlockT> {
+    pub old_block_num: <Block::Header as HeaderT>::Number,
+    pub old_block_hash: <Block::Header as HeaderT>::Hash,
+    pub new_block_num: <Block::Header as HeaderT>::Number,
+    // `None` if the new tip has not been archived.
+    pub new_block_hash: Option<<Block::Header as HeaderT>::Hash>,
+    // in descending order of the block number.
+    pub retracted: Vec<<Block::Header as HeaderT>::Hash>,
+}
+
+/// The kind of payload, which decides the topic that the payload is published to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PayloadKind {
+    Metadata,
+    Block,
+    BestBlock,
+    FinalizedBlock,
+    Rollback,
+}
+
+impl PayloadKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Metadata => "metadata",
+            Self::Block => "block",
+            Self::BestBlock => "best_block",
+            Self::FinalizedBlock => "finalized_block",
+            Self::Rollback => "rollback",
+        }
+    }
+}
+
+impl fmt::Display for PayloadKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PayloadKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "metadata" => Ok(Self::Metadata),
+            "block" => Ok(Self::Block),
+            "best_block" => Ok(Self::BestBlock),
+            "finalized_block" => Ok(Self::FinalizedBlock),
+            "rollback" => Ok(Self::Rollback),
+            _ => Err(format!("Unknown payload kind `{}`", s)),
+        }
+    }
+}
+
+// The best and finalized blocks are keyed by the constant keys, so that every update is in the
+// same partition and consumed in order, e.g. the re-organized best block with a lower number
+// never precedes the replaced one.
+pub(crate) const BEST_BLOCK_KEY: &str = "best_block";
+pub(crate) const FINALIZED_BLOCK_KEY: &str = "finalized_block";
+
+/// The serialized payload with its kind and key, which can be stored before being published.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PayloadRecord {
+    pub kind: PayloadKind,
+    pub key: Option<String>,
+    pub payload: String,
+}
+
+impl<B: BlockT> From<MetadataPayload<B>> for PayloadRecord {
+    fn from(metadata: MetadataPayload<B>) -> Self {
+        Self {
+            kind: PayloadKind::Metadata,
+            key: Some(metadata.version.to_string()),
+            payload: serde_json::to_string(&metadata)
+                .expect("Serialize metadata payload shouldn't be fail; qed"),
+        }
+    }
+}
+
+impl<B: BlockT> From<BlockPayload<B>> for PayloadRecord {
+    fn from(block: BlockPayload<B>) -> Self {
+        Self {
+            kind: PayloadKind::Block,
+            key: Some(block.block_num.to_string()),
+            payload: serde_json::to_string(&block)
+                .expect("Serialize block payload shouldn't be fail; qed"),
+        }
+    }
+}
+
+impl<B: BlockT> From<BestBlockPayload<B>> for PayloadRecord {
+    fn from(best_block: BestBlockPayload<B>) -> Self {
+        Self {
+            kind: PayloadKind::BestBlock,
+            key: Some(BEST_BLOCK_KEY.into()),
+            payload: serde_json::to_string(&best_block)
+                .expect("Serialize best block payload shouldn't be fail; qed"),
+        }
+    }
+}
+
+impl<B: BlockT> From<FinalizedBlockPayload<B>> for PayloadRecord {
+    fn from(finalized_block: FinalizedBlockPayload<B>) -> Self {
+        Self {
+            kind: PayloadKind::FinalizedBlock,
+            key: Some(FINALIZED_BLOCK_KEY.into()),
+            payload: serde_json::to_string(&finalized_block)
+                .expect("Serialize finalized block payload shouldn't be fail; qed"),
+        }
+    }
+}
+
+impl<B: BlockT> From<RollbackPayload<B>> for PayloadRecord {
+    fn from(rollback: RollbackPayload<B>) -> Self {
+        Self {
+            kind: PayloadKind::Rollback,
+            key: None,
+            payload: serde_json::to_string(&rollback)
+                .expect("Serialize rollback payload shouldn't be fail; qed"),
+        }
+    }
+}
+
 // only for example `demo`
 #[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -104,3 +230,48 @@ pub struct FinalizedBlockPayloadDemo {
     pub block_hash: String,
     pub timestamp: i64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::H256;
+    use sp_runtime::testing::{Block as TestBlock, ExtrinsicWrapper};
+
+    type Block = TestBlock<ExtrinsicWrapper<u64>>;
+
+    #[test]
+    fn payload_kind_round_trip() {
+        for kind in [
+            PayloadKind::Metadata,
+            PayloadKind::Block,
+            PayloadKind::BestBlock,
+            PayloadKind::FinalizedBlock,
+            PayloadKind::Rollback,
+        ] {
+            assert_eq!(kind.to_string().parse::<PayloadKind>(), Ok(kind));
+        }
+        assert!("unknown".parse::<PayloadKind>().is_err());
+    }
+
+    #[test]
+    fn best_and_finalized_keys() {
+        for block_num in [1, 2] {
+            let best: PayloadRecord = BestBlockPayload::<Block> {
+                block_num,
+                block_hash: H256::repeat_byte(1),
+            }
+            .into();
+            assert_eq!(best.kind, PayloadKind::BestBlock);
+            assert_eq!(best.key.as_deref(), Some("best_block"));
+
+            let finalized: PayloadRecord = FinalizedBlockPayload::<Block> {
+                block_num,
+                block_hash: H256::repeat_byte(1),
+                timestamp: 0,
+            }
+            .into();
+            assert_eq!(finalized.kind, PayloadKind::FinalizedBlock);
+            assert_eq!(finalized.key.as_deref(), Some("finalized_block"));
+        }
+    }
+}
@@ -6,8 +6,6 @@ use rdkafka::{
     producer::{FutureProducer, FutureRecord},
 };
 
-use sp_runtime::traits::Block as BlockT;
-
 use crate::{config::KafkaConfig, payload::*};
 
 #[derive(Clone)]
@@ -38,6 +36,8 @@ impl KafkaProducer {
             || config.rdkafka.get("bootstrap.servers").is_some())
             && !config.topic.metadata.is_empty()
             && !config.topic.block.is_empty()
+            && !config.topic.best_block.is_empty()
+            && !config.topic.rollback.is_empty()
     }
 
     pub async fn send(&self, payload: impl SendPayload) -> Result<(), KafkaError> {
@@ -88,7 +88,23 @@ pub trait SendPayload: Send + Sized {
 }
 
 #[async_trait::async_trait]
-impl<B: BlockT> SendPayload for MetadataPayload<B> {
+impl SendPayload for PayloadRecord {
+    async fn send(self, producer: &KafkaProducer) -> Result<(), KafkaError> {
+        log::debug!(
+            target: "kafka",
+            "Publish {} record to kafka, key = {:?}",
+            self.kind,
+            self.key
+        );
+        let topic = producer.config.topic.topic(self.kind);
+        producer
+            .send_inner(topic, &self.payload, self.key.as_deref())
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl SendPayload for MetadataPayloadForDemo {
     async fn send(self, producer: &KafkaProducer) -> Result<(), KafkaError> {
         log::info!(
             target: "kafka",
@@ -104,7 +120,7 @@ impl<B: BlockT> SendPayload for MetadataPayload<B> {
 }
 
 #[async_trait::async_trait]
-impl<B: BlockT> SendPayload for BlockPayload<B> {
+impl SendPayload for BlockPayloadForDemo {
     async fn send(self, producer: &KafkaProducer) -> Result<(), KafkaError> {
         log::info!(
             target: "kafka",
@@ -121,51 +137,20 @@ impl<B: BlockT> SendPayload for BlockPayload<B> {
 }
 
 #[async_trait::async_trait]
-impl<B: BlockT> SendPayload for FinalizedBlockPayload<B> {
+impl SendPayload for BestBlockPayloadDemo {
     async fn send(self, producer: &KafkaProducer) -> Result<(), KafkaError> {
         log::info!(
             target: "kafka",
-            "Publish finalized block to kafka, number = {}, hash = {}",
+            "Publish best block to kafka, number = {}, hash = {}",
             self.block_num,
             self.block_hash
         );
-        let topic = &producer.config.topic.finalized_block;
-        let payload = serde_json::to_string(&self)
-            .expect("Serialize finalized block payload shouldn't be fail; qed");
-        producer.send_inner(topic, &payload, None).await
-    }
-}
-
-#[async_trait::async_trait]
-impl SendPayload for MetadataPayloadForDemo {
-    async fn send(self, producer: &KafkaProducer) -> Result<(), KafkaError> {
-        log::info!(
-            target: "kafka",
-            "Publish metadata to kafka, version = {}",
-            self.version
-        );
-        let topic = &producer.config.topic.metadata;
-        let payload = serde_json::to_string(&self)
-            .expect("Serialize metadata payload shouldn't be fail; qed");
-        let key = self.version.to_string();
-        producer.send_inner(topic, &payload, Some(&key)).await
-    }
-}
-
-#[async_trait::async_trait]
-impl SendPayload for BlockPayloadForDemo {
-    async fn send(self, producer: &KafkaProducer) -> Result<(), KafkaError> {
-        log::info!(
-            target: "kafka",
-            "Publish block to kafka, number = {}, hash = {}",
-            self.block_num,
-            self.block_hash
-        );
-        let topic = &producer.config.topic.block;
+        let topic = &producer.config.topic.best_block;
         let payload = serde_json::to_string(&self)
             .expect("Serialize best block payload shouldn't be fail; qed");
-        let key = self.block_num.to_string();
-        producer.send_inner(topic, &payload, Some(&key)).await
+        producer
+            .send_inner(topic, &payload, Some(BEST_BLOCK_KEY))
+            .await
     }
 }
 
@@ -181,6 +166,8 @@ impl SendPayload for FinalizedBlockPayloadDemo {
         let topic = &producer.config.topic.finalized_block;
         let payload = serde_json::to_string(&self)
             .expect("Serialize finalized block payload shouldn't be fail; qed");
-        producer.send_inner(topic, &payload, None).await
+        producer
+            .send_inner(topic, &payload, Some(FINALIZED_BLOCK_KEY))
+            .await
     }
 }
@@ -12,6 +12,7 @@ async fn main() -> Result<(), SqlxError> {
         idle_timeout: Some(10 * 60),
         max_lifetime: Some(30 * 60),
         disable_statement_logging: true,
+        partition_width: None,
     };
 
     migrate(config.uri()).await?;
@@ -56,6 +57,7 @@ async fn main() -> Result<(), SqlxError> {
                 prefix: vec![count],
                 key: vec![count],
                 data: Some(vec![count]),
+                kind: "unknown".to_string(),
             })
             .collect::<Vec<_>>();
         let _ = db.insert(storages).await?;
@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS child_storage (
+    block_num integer CHECK (block_num >= 0) NOT NULL REFERENCES block (block_num),
+    block_hash bytea NOT NULL,
+
+    prefix_key bytea NOT NULL,
+    key bytea NOT NULL,
+    data bytea,
+
+    PRIMARY KEY (block_num, prefix_key, key)
+) PARTITION BY RANGE (block_num);
+
+-- The partitions are created by `PostgresDb::ensure_partitions` ahead of the blocks.
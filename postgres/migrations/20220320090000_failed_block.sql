@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS failed_block (
+    block_num integer CHECK (block_num >= 0) NOT NULL,
+
+    attempts integer NOT NULL,
+    error text NOT NULL,
+    failed_at timestamptz NOT NULL DEFAULT now(),
+
+    PRIMARY KEY (block_num)
+);
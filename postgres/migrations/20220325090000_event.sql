@@ -0,0 +1,21 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS event (
+    block_num integer CHECK (block_num >= 0) NOT NULL REFERENCES block (block_num),
+    block_hash bytea NOT NULL,
+    event_index integer CHECK (event_index >= 0) NOT NULL,
+
+    -- `ApplyExtrinsic`, `Finalization` or `Initialization`
+    phase text NOT NULL,
+    extrinsic_index integer CHECK (extrinsic_index >= 0),
+
+    pallet text NOT NULL,
+    variant text NOT NULL,
+    topics bytea[] NOT NULL,
+    fields jsonb NOT NULL,
+
+    PRIMARY KEY (block_num, event_index)
+) PARTITION BY RANGE (block_num);
+
+-- The partitions are created on demand.
+
+CREATE INDEX IF NOT EXISTS event_pallet_variant_idx ON event (pallet, variant);
@@ -0,0 +1,28 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS extrinsic (
+    block_num integer CHECK (block_num >= 0) NOT NULL REFERENCES block (block_num),
+    block_hash bytea NOT NULL,
+    extrinsic_index integer CHECK (extrinsic_index >= 0) NOT NULL,
+    hash bytea NOT NULL,
+
+    is_signed boolean NOT NULL,
+    signer bytea,
+    nonce integer CHECK (nonce >= 0),
+    tip numeric,
+    era jsonb,
+
+    pallet text,
+    call text,
+    args jsonb,
+
+    -- The raw bytes of the extrinsic that can't be decoded.
+    raw bytea,
+
+    PRIMARY KEY (block_num, extrinsic_index)
+) PARTITION BY RANGE (block_num);
+
+-- The partitions are created on demand.
+
+CREATE INDEX IF NOT EXISTS extrinsic_hash_idx ON extrinsic (hash);
+CREATE INDEX IF NOT EXISTS extrinsic_signer_idx ON extrinsic (signer);
+CREATE INDEX IF NOT EXISTS extrinsic_pallet_call_idx ON extrinsic (pallet, call);
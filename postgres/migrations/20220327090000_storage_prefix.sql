@@ -0,0 +1,23 @@
+-- Add migration script here
+-- Twox128(pallet prefix) ++ Twox128(storage entry name) => names, built from every metadata version.
+CREATE TABLE IF NOT EXISTS storage_prefix (
+    prefix bytea NOT NULL,
+    pallet text NOT NULL,
+    item text NOT NULL,
+
+    PRIMARY KEY (prefix)
+);
+
+CREATE INDEX IF NOT EXISTS storage_prefix_pallet_item_idx ON storage_prefix (pallet, item);
+
+-- The decoded arguments of the map keys, which are only decodable with the transparent hashers
+-- (Blake2_128Concat, Twox64Concat and Identity), the opaque hashed arguments are `null`.
+CREATE TABLE IF NOT EXISTS storage_key (
+    key bytea NOT NULL,
+    prefix bytea NOT NULL,
+    args jsonb NOT NULL,
+
+    PRIMARY KEY (key)
+);
+
+CREATE INDEX IF NOT EXISTS storage_key_prefix_idx ON storage_key (prefix);
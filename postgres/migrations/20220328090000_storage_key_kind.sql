@@ -0,0 +1,4 @@
+-- Add migration script here
+-- The kind of main storage key: `well_known`, `pallet_value`, `pallet_map`, `child_trie_root` or `unknown`,
+-- the keys archived before are `unknown`.
+ALTER TABLE main_storage ADD COLUMN IF NOT EXISTS kind text NOT NULL DEFAULT 'unknown';
@@ -0,0 +1,31 @@
+-- Add migration script here
+-- The `:code` blobs, deduplicated by the hash.
+CREATE TABLE IF NOT EXISTS runtime_code (
+    code_hash bytea NOT NULL,
+    code bytea NOT NULL,
+
+    PRIMARY KEY (code_hash)
+);
+
+-- The runtime upgrade history, every runtime code is active within [first_block, last_block],
+-- a code that becomes active again after another one starts a new range.
+CREATE TABLE IF NOT EXISTS runtime_version (
+    code_hash bytea NOT NULL REFERENCES runtime_code (code_hash),
+
+    spec_name text NOT NULL,
+    impl_name text NOT NULL,
+    authoring_version integer CHECK (authoring_version >= 0) NOT NULL,
+    spec_version integer CHECK (spec_version >= 0) NOT NULL,
+    impl_version integer CHECK (impl_version >= 0) NOT NULL,
+    transaction_version integer CHECK (transaction_version >= 0) NOT NULL,
+    -- [["0x{api id}", api version], ...]
+    apis jsonb NOT NULL,
+
+    first_block integer CHECK (first_block >= 0) NOT NULL,
+    last_block integer CHECK (last_block >= first_block) NOT NULL,
+
+    PRIMARY KEY (code_hash, first_block)
+);
+
+CREATE INDEX IF NOT EXISTS runtime_version_spec_version_idx ON runtime_version (spec_version);
+CREATE INDEX IF NOT EXISTS runtime_version_block_idx ON runtime_version (first_block, last_block);
@@ -0,0 +1,64 @@
+-- Add migration script here
+-- Keep the blocks of all forks, which are identified by (block_num, block_hash),
+-- only one block of every number is canonical, the others are orphaned by rollbacks.
+--
+-- NOTE: this changes the contract of rollback. Before, `PostgresDb::delete` removed the blocks
+-- above the rollback point together with their main_storage, child_storage and event rows.
+-- `PostgresDb::delete` no longer exists, `PostgresDb::orphan_blocks` only marks the blocks
+-- non-canonical, and the rows of main_storage, child_storage, event, extrinsic and storage_key
+-- derived from them are kept. Query the `canonical_*` views below to see the canonical chain only.
+ALTER TABLE block ADD COLUMN IF NOT EXISTS canonical boolean NOT NULL DEFAULT true;
+
+ALTER TABLE main_storage DROP CONSTRAINT IF EXISTS main_storage_block_num_fkey;
+ALTER TABLE child_storage DROP CONSTRAINT IF EXISTS child_storage_block_num_fkey;
+ALTER TABLE event DROP CONSTRAINT IF EXISTS event_block_num_fkey;
+ALTER TABLE extrinsic DROP CONSTRAINT IF EXISTS extrinsic_block_num_fkey;
+
+ALTER TABLE block DROP CONSTRAINT IF EXISTS block_pkey;
+ALTER TABLE block ADD PRIMARY KEY (block_num, block_hash);
+CREATE UNIQUE INDEX IF NOT EXISTS block_canonical_idx ON block (block_num) WHERE canonical;
+CREATE INDEX IF NOT EXISTS block_hash_idx ON block (block_hash);
+
+ALTER TABLE main_storage DROP CONSTRAINT IF EXISTS main_storage_pkey;
+ALTER TABLE main_storage ADD PRIMARY KEY (block_num, block_hash, key);
+ALTER TABLE main_storage ADD FOREIGN KEY (block_num, block_hash) REFERENCES block (block_num, block_hash);
+
+ALTER TABLE child_storage DROP CONSTRAINT IF EXISTS child_storage_pkey;
+ALTER TABLE child_storage ADD PRIMARY KEY (block_num, block_hash, prefix_key, key);
+ALTER TABLE child_storage ADD FOREIGN KEY (block_num, block_hash) REFERENCES block (block_num, block_hash);
+
+ALTER TABLE event DROP CONSTRAINT IF EXISTS event_pkey;
+ALTER TABLE event ADD PRIMARY KEY (block_num, block_hash, event_index);
+ALTER TABLE event ADD FOREIGN KEY (block_num, block_hash) REFERENCES block (block_num, block_hash);
+
+ALTER TABLE extrinsic DROP CONSTRAINT IF EXISTS extrinsic_pkey;
+ALTER TABLE extrinsic ADD PRIMARY KEY (block_num, block_hash, extrinsic_index);
+ALTER TABLE extrinsic ADD FOREIGN KEY (block_num, block_hash) REFERENCES block (block_num, block_hash);
+
+-- The rows of the canonical chain, the rows of the orphaned blocks are only reachable through
+-- the non-canonical blocks.
+CREATE OR REPLACE VIEW canonical_block AS
+    SELECT * FROM block WHERE canonical;
+
+CREATE OR REPLACE VIEW canonical_main_storage AS
+    SELECT main_storage.* FROM main_storage
+    JOIN block USING (block_num, block_hash) WHERE block.canonical;
+
+CREATE OR REPLACE VIEW canonical_child_storage AS
+    SELECT child_storage.* FROM child_storage
+    JOIN block USING (block_num, block_hash) WHERE block.canonical;
+
+CREATE OR REPLACE VIEW canonical_event AS
+    SELECT event.* FROM event
+    JOIN block USING (block_num, block_hash) WHERE block.canonical;
+
+CREATE OR REPLACE VIEW canonical_extrinsic AS
+    SELECT extrinsic.* FROM extrinsic
+    JOIN block USING (block_num, block_hash) WHERE block.canonical;
+
+-- The storage_key rows are shared by blocks, the keys changed only by orphaned blocks are excluded.
+CREATE OR REPLACE VIEW canonical_storage_key AS
+    SELECT storage_key.* FROM storage_key WHERE EXISTS (
+        SELECT 1 FROM main_storage JOIN block USING (block_num, block_hash)
+        WHERE block.canonical AND main_storage.key = storage_key.key
+    );
@@ -0,0 +1,18 @@
+-- Add migration script here
+-- The payloads to the sinks, written in the same transaction as the data they are derived from,
+-- and published by the relay of every sink in the order of id.
+CREATE TABLE IF NOT EXISTS outbox (
+    id bigserial PRIMARY KEY,
+
+    kind text NOT NULL,
+    key text,
+    payload text NOT NULL,
+
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+-- The id of the last outbox row that the sink has published.
+CREATE TABLE IF NOT EXISTS outbox_offset (
+    sink text PRIMARY KEY,
+    last_id bigint NOT NULL DEFAULT 0
+);
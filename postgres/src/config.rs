@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::database::DEFAULT_PARTITION_WIDTH;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PostgresConfig {
     // connection options (parse for the PgConnectOptions)
@@ -12,10 +14,18 @@ pub struct PostgresConfig {
     pub max_lifetime: Option<u64>, // seconds
     // Entirely disables statement logging (both slow and regular).
     pub disable_statement_logging: bool,
+    // The number of blocks that a partition of the partitioned tables contains.
+    pub partition_width: Option<u32>,
 }
 
 impl PostgresConfig {
     pub fn uri(&self) -> &str {
         &self.uri
     }
+
+    pub fn partition_width(&self) -> u32 {
+        self.partition_width
+            .unwrap_or(DEFAULT_PARTITION_WIDTH)
+            .max(1)
+    }
 }
@@ -0,0 +1,406 @@
+//! Bulk load the models with `COPY ... FROM STDIN (FORMAT binary)`.
+//!
+//! `COPY` doesn't support `ON CONFLICT`, so the rows are copied into a temporary table first,
+//! and then upserted into the target table with `INSERT INTO ... SELECT ... ON CONFLICT`.
+//! See https://www.postgresql.org/docs/current/sql-copy.html#id-1.9.3.55.9.4 for the binary format.
+
+use sqlx::{error::Error as SqlxError, postgres::PgConnection, Connection};
+
+use crate::{database::insert::*, model::*};
+
+/// The number of rows above which `InsertModel` for `Vec<T>` switches to `COPY`.
+pub const COPY_THRESHOLD: usize = 5_000;
+
+// The size of data that sent to postgres in a single `CopyData` message.
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+// The OID of `bytea` type.
+const BYTEA_OID: i32 = 17;
+
+/// The buffer of `COPY` binary format.
+pub struct BinaryCopyBuffer {
+    buf: Vec<u8>,
+}
+
+impl BinaryCopyBuffer {
+    fn new() -> Self {
+        let mut buf = Vec::with_capacity(COPY_CHUNK_SIZE);
+        // signature
+        buf.extend_from_slice(b"PGCOPY\n\xff\r\n\0");
+        // flags field
+        buf.extend_from_slice(&0i32.to_be_bytes());
+        // header extension area length
+        buf.extend_from_slice(&0i32.to_be_bytes());
+        Self { buf }
+    }
+
+    fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn take(&mut self) -> Vec<u8> {
+        std::mem::replace(&mut self.buf, Vec::with_capacity(COPY_CHUNK_SIZE))
+    }
+
+    fn trailer(&mut self) {
+        self.buf.extend_from_slice(&(-1i16).to_be_bytes());
+    }
+
+    pub fn row(&mut self, fields: i16) {
+        self.buf.extend_from_slice(&fields.to_be_bytes());
+    }
+
+    pub fn int4(&mut self, value: u32) {
+        self.buf.extend_from_slice(&4i32.to_be_bytes());
+        self.buf.extend_from_slice(&(value as i32).to_be_bytes());
+    }
+
+    pub fn bytea(&mut self, value: &[u8]) {
+        self.buf
+            .extend_from_slice(&(value.len() as i32).to_be_bytes());
+        self.buf.extend_from_slice(value);
+    }
+
+    pub fn text(&mut self, value: &str) {
+        self.bytea(value.as_bytes());
+    }
+
+    pub fn null(&mut self) {
+        self.buf.extend_from_slice(&(-1i32).to_be_bytes());
+    }
+
+    pub fn optional_bytea(&mut self, value: Option<&[u8]>) {
+        match value {
+            Some(value) => self.bytea(value),
+            None => self.null(),
+        }
+    }
+
+    pub fn bytea_array(&mut self, values: &[Vec<u8>]) {
+        let ndim = if values.is_empty() { 0i32 } else { 1i32 };
+        let len = 12
+            + if values.is_empty() { 0 } else { 8 }
+            + values.iter().map(|value| 4 + value.len()).sum::<usize>();
+        self.buf.extend_from_slice(&(len as i32).to_be_bytes());
+        // number of dimensions, has-null flag, element type
+        self.buf.extend_from_slice(&ndim.to_be_bytes());
+        self.buf.extend_from_slice(&0i32.to_be_bytes());
+        self.buf.extend_from_slice(&BYTEA_OID.to_be_bytes());
+        if !values.is_empty() {
+            // dimension size and lower bound
+            self.buf
+                .extend_from_slice(&(values.len() as i32).to_be_bytes());
+            self.buf.extend_from_slice(&1i32.to_be_bytes());
+        }
+        for value in values {
+            self.bytea(value);
+        }
+    }
+
+    pub fn optional_bytea_array(&mut self, values: Option<&[Vec<u8>]>) {
+        match values {
+            Some(values) => self.bytea_array(values),
+            None => self.null(),
+        }
+    }
+}
+
+fn encode_block(buf: &mut BinaryCopyBuffer, model: BlockModel) {
+    buf.row(9);
+    buf.int4(model.version);
+    buf.int4(model.block_num);
+    buf.bytea(&model.block_hash);
+    buf.bytea(&model.parent_hash);
+    buf.bytea(&model.state_root);
+    buf.bytea(&model.extrinsics_root);
+    buf.bytea(&model.digest);
+    buf.bytea_array(&model.extrinsics);
+    buf.optional_bytea_array(model.justifications.as_deref());
+}
+
+fn encode_main_storage(buf: &mut BinaryCopyBuffer, model: MainStorageChangeModel) {
+    buf.row(6);
+    buf.int4(model.block_num);
+    buf.bytea(&model.block_hash);
+    buf.bytea(&model.prefix);
+    buf.bytea(&model.key);
+    buf.optional_bytea(model.data.as_deref());
+    buf.text(&model.kind);
+}
+
+fn encode_child_storage(buf: &mut BinaryCopyBuffer, model: ChildStorageChangeModel) {
+    buf.row(5);
+    buf.int4(model.block_num);
+    buf.bytea(&model.block_hash);
+    buf.bytea(&model.prefix_key);
+    buf.bytea(&model.key);
+    buf.optional_bytea(model.data.as_deref());
+}
+
+/// Copy the rows into `table` and upsert them with the `on_conflict` clause.
+async fn copy_upsert<T: Send>(
+    conn: &mut PgConnection,
+    table: &str,
+    columns: &str,
+    on_conflict: &str,
+    rows: Vec<T>,
+    encode: impl Fn(&mut BinaryCopyBuffer, T) + Send,
+) -> Result<u64, SqlxError> {
+    let mut tx = conn.begin().await?;
+    sqlx::query(&format!(
+        "CREATE TEMP TABLE {table}_copy (LIKE {table} INCLUDING DEFAULTS) ON COMMIT DROP",
+        table = table
+    ))
+    .execute(&mut tx)
+    .await?;
+
+    let mut copy = tx
+        .copy_in_raw(&format!(
+            "COPY {}_copy ({}) FROM STDIN (FORMAT binary)",
+            table, columns
+        ))
+        .await?;
+    let mut buf = BinaryCopyBuffer::new();
+    for row in rows {
+        encode(&mut buf, row);
+        if buf.len() >= COPY_CHUNK_SIZE {
+            copy.send(buf.take()).await?;
+        }
+    }
+    buf.trailer();
+    copy.send(buf.take()).await?;
+    copy.finish().await?;
+
+    let rows_affected = sqlx::query(&format!(
+        "INSERT INTO {table} ({columns}) SELECT {columns} FROM {table}_copy {on_conflict}",
+        table = table,
+        columns = columns,
+        on_conflict = on_conflict,
+    ))
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+    // drop the temporary table explicitly, the outer transaction may copy the same table again.
+    sqlx::query(&format!("DROP TABLE {}_copy", table))
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(rows_affected)
+}
+
+#[async_trait::async_trait]
+pub trait CopyModel: Send + Sized {
+    async fn copy(self, conn: &mut PgConnection) -> Result<u64, SqlxError>;
+}
+
+#[async_trait::async_trait]
+impl CopyModel for Vec<BlockModel> {
+    async fn copy(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        log::debug!(
+            target: "postgres",
+            "Copy bulk block into postgres, height = [{:?}~{:?}]",
+            self.first().map(|block| block.block_num),
+            self.last().map(|block| block.block_num)
+        );
+
+        let rows_affected = copy_upsert(
+            conn,
+            "block",
+            BLOCK_COLUMNS,
+            BLOCK_ON_CONFLICT,
+            self,
+            encode_block,
+        )
+        .await?;
+
+        log::debug!(
+            target: "postgres",
+            "Copy bulk block into postgres, affected rows = {}",
+            rows_affected
+        );
+        Ok(rows_affected)
+    }
+}
+
+#[async_trait::async_trait]
+impl CopyModel for Vec<MainStorageChangeModel> {
+    async fn copy(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        log::debug!(
+            target: "postgres",
+            "Copy bulk main storage into postgres, height = [{:?}~{:?}]",
+            self.first().map(|storage| storage.block_num),
+            self.last().map(|storage| storage.block_num)
+        );
+
+        let rows_affected = copy_upsert(
+            conn,
+            "main_storage",
+            MAIN_STORAGE_COLUMNS,
+            MAIN_STORAGE_ON_CONFLICT,
+            self,
+            encode_main_storage,
+        )
+        .await?;
+
+        log::debug!(
+            target: "postgres",
+            "Copy bulk main storage into postgres, affected rows = {}",
+            rows_affected
+        );
+        Ok(rows_affected)
+    }
+}
+
+#[async_trait::async_trait]
+impl CopyModel for Vec<ChildStorageChangeModel> {
+    async fn copy(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        log::debug!(
+            target: "postgres",
+            "Copy bulk child storage into postgres, height = [{:?}~{:?}]",
+            self.first().map(|storage| storage.block_num),
+            self.last().map(|storage| storage.block_num)
+        );
+
+        let rows_affected = copy_upsert(
+            conn,
+            "child_storage",
+            CHILD_STORAGE_COLUMNS,
+            CHILD_STORAGE_ON_CONFLICT,
+            self,
+            encode_child_storage,
+        )
+        .await?;
+
+        log::debug!(
+            target: "postgres",
+            "Copy bulk child storage into postgres, affected rows = {}",
+            rows_affected
+        );
+        Ok(rows_affected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The length of the signature, flags field and header extension area length.
+    const HEADER_LEN: usize = 11 + 4 + 4;
+
+    fn encoded(encode: impl FnOnce(&mut BinaryCopyBuffer)) -> Vec<u8> {
+        let mut buf = BinaryCopyBuffer::new();
+        encode(&mut buf);
+        buf.take()[HEADER_LEN..].to_vec()
+    }
+
+    #[test]
+    fn encode_header_and_trailer() {
+        let mut buf = BinaryCopyBuffer::new();
+        buf.trailer();
+        assert_eq!(
+            buf.take(),
+            b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0\xff\xff".to_vec()
+        );
+    }
+
+    #[test]
+    fn encode_fields() {
+        assert_eq!(encoded(|buf| buf.row(5)), vec![0, 5]);
+        assert_eq!(encoded(|buf| buf.int4(1)), vec![0, 0, 0, 4, 0, 0, 0, 1]);
+        // the bits of u32 are kept as they are, postgres rejects the negative `block_num`.
+        assert_eq!(
+            encoded(|buf| buf.int4(u32::MAX)),
+            vec![0, 0, 0, 4, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(
+            encoded(|buf| buf.int4(i32::MAX as u32)),
+            vec![0, 0, 0, 4, 0x7f, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(encoded(|buf| buf.null()), vec![0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(encoded(|buf| buf.bytea(&[])), vec![0, 0, 0, 0]);
+        assert_eq!(
+            encoded(|buf| buf.optional_bytea(Some(&[0xab, 0xcd]))),
+            vec![0, 0, 0, 2, 0xab, 0xcd]
+        );
+        assert_eq!(
+            encoded(|buf| buf.optional_bytea(None)),
+            vec![0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(encoded(|buf| buf.text("ab")), vec![0, 0, 0, 2, b'a', b'b']);
+    }
+
+    #[test]
+    fn encode_bytea_array() {
+        assert_eq!(
+            encoded(|buf| buf.bytea_array(&[])),
+            vec![
+                0, 0, 0, 12, // length
+                0, 0, 0, 0, // ndim
+                0, 0, 0, 0, // has-null flag
+                0, 0, 0, 17, // bytea oid
+            ]
+        );
+        assert_eq!(
+            encoded(|buf| buf.bytea_array(&[vec![1], vec![]])),
+            vec![
+                0, 0, 0, 29, // length
+                0, 0, 0, 1, // ndim
+                0, 0, 0, 0, // has-null flag
+                0, 0, 0, 17, // bytea oid
+                0, 0, 0, 2, // dimension size
+                0, 0, 0, 1, // lower bound
+                0, 0, 0, 1, 1, // element
+                0, 0, 0, 0, // empty element
+            ]
+        );
+        assert_eq!(
+            encoded(|buf| buf.optional_bytea_array(None)),
+            vec![0xff, 0xff, 0xff, 0xff]
+        );
+    }
+
+    #[test]
+    fn encode_child_storage_row() {
+        let model = ChildStorageChangeModel {
+            block_num: 258,
+            block_hash: vec![0xaa],
+            prefix_key: vec![0xbb, 0xcc],
+            key: vec![],
+            data: None,
+        };
+        assert_eq!(
+            encoded(|buf| encode_child_storage(buf, model)),
+            vec![
+                0, 5, // fields
+                0, 0, 0, 4, 0, 0, 1, 2, // block_num
+                0, 0, 0, 1, 0xaa, // block_hash
+                0, 0, 0, 2, 0xbb, 0xcc, // prefix_key
+                0, 0, 0, 0, // key
+                0xff, 0xff, 0xff, 0xff, // data
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_main_storage_row() {
+        let model = MainStorageChangeModel {
+            block_num: 1,
+            block_hash: vec![0xaa],
+            prefix: vec![],
+            key: vec![0xbb],
+            data: Some(vec![0xcc]),
+            kind: "unknown".into(),
+        };
+        let mut expected = vec![
+            0, 6, // fields
+            0, 0, 0, 4, 0, 0, 0, 1, // block_num
+            0, 0, 0, 1, 0xaa, // block_hash
+            0, 0, 0, 0, // prefix
+            0, 0, 0, 1, 0xbb, // key
+            0, 0, 0, 1, 0xcc, // data
+            0, 0, 0, 7, // kind
+        ];
+        expected.extend_from_slice(b"unknown");
+        assert_eq!(encoded(|buf| encode_main_storage(buf, model)), expected);
+    }
+}
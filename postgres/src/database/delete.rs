@@ -1,58 +1,145 @@
-use sqlx::{error::Error as SqlxError, pool::PoolConnection, postgres::Postgres};
+use std::cmp::Reverse;
+
+use sqlx::{error::Error as SqlxError, postgres::PgConnection, FromRow};
 
 use crate::model::*;
 
 #[async_trait::async_trait]
 pub trait DeleteModel: Send + Sized {
-    async fn delete(conn: &mut PoolConnection<Postgres>, block_num: u32) -> Result<u64, SqlxError>;
+    async fn delete(conn: &mut PgConnection, block_num: u32) -> Result<u64, SqlxError>;
 }
 
-fn gen_delete_sql(table: &str) -> String {
-    format!("DELETE FROM {} WHERE block_num > $1", table)
+/// Mark the blocks (block_num > `block_num`) as non-canonical, the blocks and their storages,
+/// extrinsics and events are kept for studying the forks.
+///
+/// Returns the numbers and hashes of the orphaned blocks, in descending order of the number.
+pub async fn orphan_blocks(
+    conn: &mut PgConnection,
+    block_num: u32,
+) -> Result<Vec<(u32, Vec<u8>)>, SqlxError> {
+    #[derive(Clone, Debug, Eq, PartialEq, FromRow)]
+    struct OrphanedBlock {
+        block_num: i32,
+        block_hash: Vec<u8>,
+    }
+
+    let mut blocks: Vec<OrphanedBlock> = sqlx::query_as(
+        "UPDATE block SET canonical = false WHERE canonical AND block_num > $1 RETURNING block_num, block_hash",
+    )
+    .bind(block_num)
+    .fetch_all(conn)
+    .await?;
+    blocks.sort_unstable_by_key(|block| Reverse(block.block_num));
+    log::info!(
+        target: "postgres",
+        "Orphan block (block_num > {}) in postgres, affected rows = {}",
+        block_num,
+        blocks.len()
+    );
+    Ok(blocks
+        .into_iter()
+        .map(|block| (block.block_num as u32, block.block_hash))
+        .collect())
 }
 
-#[async_trait::async_trait]
-impl DeleteModel for MetadataModel {
-    async fn delete(conn: &mut PoolConnection<Postgres>, block_num: u32) -> Result<u64, SqlxError> {
-        let sql = gen_delete_sql("metadata");
-        let query = sqlx::query(&sql).bind(block_num);
-        let rows_affected = query.execute(conn).await?.rows_affected();
-        log::info!(
-            target: "postgres",
-            "Delete metadata (block_num > {}) from postgres, affected rows = {}",
-            block_num,
-            rows_affected
-        );
-        Ok(rows_affected)
-    }
+/// Remove the offsets of the sinks that are not in `sinks`, the outbox rows that only they
+/// haven't published are pruned then.
+pub async fn unregister_outbox_sinks(
+    conn: &mut PgConnection,
+    sinks: Vec<String>,
+) -> Result<u64, SqlxError> {
+    let rows_affected = sqlx::query("DELETE FROM outbox_offset WHERE sink <> ALL($1)")
+        .bind(sinks)
+        .execute(conn)
+        .await?
+        .rows_affected();
+    log::info!(
+        target: "postgres",
+        "Delete the offsets of unconfigured sinks from postgres, affected rows = {}",
+        rows_affected
+    );
+    Ok(rows_affected)
+}
+
+/// Remove the failure records of the blocks, which have been crawled successfully.
+pub async fn clear_failed_blocks(
+    conn: &mut PgConnection,
+    block_nums: Vec<u32>,
+) -> Result<u64, SqlxError> {
+    let block_nums = block_nums
+        .into_iter()
+        .map(|num| num as i32)
+        .collect::<Vec<_>>();
+    let rows_affected = sqlx::query("DELETE FROM failed_block WHERE block_num = ANY($1)")
+        .bind(block_nums)
+        .execute(conn)
+        .await?
+        .rows_affected();
+    log::debug!(
+        target: "postgres",
+        "Delete the failed blocks from postgres, affected rows = {}",
+        rows_affected
+    );
+    Ok(rows_affected)
+}
+
+/// Remove the outbox rows that have been published by all sinks.
+pub async fn prune_outbox(conn: &mut PgConnection) -> Result<u64, SqlxError> {
+    let rows_affected =
+        sqlx::query("DELETE FROM outbox WHERE id <= (SELECT MIN(last_id) FROM outbox_offset)")
+            .execute(conn)
+            .await?
+            .rows_affected();
+    log::debug!(
+        target: "postgres",
+        "Prune published outbox from postgres, affected rows = {}",
+        rows_affected
+    );
+    Ok(rows_affected)
 }
 
 #[async_trait::async_trait]
-impl DeleteModel for BlockModel {
-    async fn delete(conn: &mut PoolConnection<Postgres>, block_num: u32) -> Result<u64, SqlxError> {
-        let sql = gen_delete_sql("block");
-        let query = sqlx::query(&sql).bind(block_num);
-        let rows_affected = query.execute(conn).await?.rows_affected();
+impl DeleteModel for RuntimeVersionModel {
+    async fn delete(conn: &mut PgConnection, block_num: u32) -> Result<u64, SqlxError> {
+        // the runtimes started after the block are removed, the others are cut off at the block.
+        let deleted = sqlx::query("DELETE FROM runtime_version WHERE first_block > $1")
+            .bind(block_num)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+        let updated =
+            sqlx::query("UPDATE runtime_version SET last_block = $1 WHERE last_block > $1")
+                .bind(block_num)
+                .execute(&mut *conn)
+                .await?
+                .rows_affected();
         log::info!(
             target: "postgres",
-            "Delete block (block_num > {}) from postgres, affected rows = {}",
+            "Delete runtime_version (first_block > {}) from postgres, affected rows = {}",
             block_num,
-            rows_affected
+            deleted + updated
         );
-        Ok(rows_affected)
+        Ok(deleted + updated)
     }
 }
 
 #[async_trait::async_trait]
-impl DeleteModel for MainStorageChangeModel {
-    async fn delete(conn: &mut PoolConnection<Postgres>, block_num: u32) -> Result<u64, SqlxError> {
-        let sql = gen_delete_sql("main_storage");
-        let query = sqlx::query(&sql).bind(block_num);
-        let rows_affected = query.execute(conn).await?.rows_affected();
+impl DeleteModel for RuntimeCodeModel {
+    async fn delete(conn: &mut PgConnection, _block_num: u32) -> Result<u64, SqlxError> {
+        // the code is shared by the runtime versions, remove it when no runtime uses it.
+        let rows_affected = sqlx::query(
+            r#"
+            DELETE FROM runtime_code WHERE NOT EXISTS (
+                SELECT 1 FROM runtime_version WHERE runtime_version.code_hash = runtime_code.code_hash
+            )
+            "#,
+        )
+        .execute(conn)
+        .await?
+        .rows_affected();
         log::info!(
             target: "postgres",
-            "Delete main_storage (block_num > {}) from postgres, affected rows = {}",
-            block_num,
+            "Delete unused runtime_code from postgres, affected rows = {}",
             rows_affected
         );
         Ok(rows_affected)
@@ -1,20 +1,115 @@
 use sqlx::{
     error::Error as SqlxError,
-    pool::PoolConnection,
-    postgres::{PgArguments, Postgres},
+    postgres::{PgArguments, PgConnection, Postgres},
     query::Query,
 };
 
-use crate::{database::batch::Batch, model::*};
+use crate::{
+    database::{
+        batch::Batch,
+        copy::{CopyModel, COPY_THRESHOLD},
+    },
+    model::*,
+};
+
+pub(crate) const BLOCK_COLUMNS: &str = "version, block_num, block_hash, parent_hash, state_root, \
+    extrinsics_root, digest, extrinsics, justifications";
+pub(crate) const BLOCK_ON_CONFLICT: &str = r#"
+    ON CONFLICT (block_num, block_hash) DO UPDATE SET
+        version = EXCLUDED.version,
+        block_num = EXCLUDED.block_num,
+        block_hash = EXCLUDED.block_hash,
+        parent_hash = EXCLUDED.parent_hash,
+        state_root = EXCLUDED.state_root,
+        extrinsics_root = EXCLUDED.extrinsics_root,
+        digest = EXCLUDED.digest,
+        extrinsics = EXCLUDED.extrinsics,
+        justifications = EXCLUDED.justifications,
+        canonical = EXCLUDED.canonical
+    "#;
+
+pub(crate) const MAIN_STORAGE_COLUMNS: &str = "block_num, block_hash, prefix, key, data, kind";
+pub(crate) const MAIN_STORAGE_ON_CONFLICT: &str = r#"
+    ON CONFLICT (block_num, block_hash, key) DO UPDATE SET
+        block_num = EXCLUDED.block_num,
+        block_hash = EXCLUDED.block_hash,
+        prefix = EXCLUDED.prefix,
+        key = EXCLUDED.key,
+        data = EXCLUDED.data,
+        kind = EXCLUDED.kind
+    "#;
+
+pub(crate) const CHILD_STORAGE_COLUMNS: &str = "block_num, block_hash, prefix_key, key, data";
+pub(crate) const CHILD_STORAGE_ON_CONFLICT: &str = r#"
+    ON CONFLICT (block_num, block_hash, prefix_key, key) DO UPDATE SET
+        block_num = EXCLUDED.block_num,
+        block_hash = EXCLUDED.block_hash,
+        prefix_key = EXCLUDED.prefix_key,
+        key = EXCLUDED.key,
+        data = EXCLUDED.data
+    "#;
+
+pub(crate) const EXTRINSIC_ON_CONFLICT: &str = r#"
+    ON CONFLICT (block_num, block_hash, extrinsic_index) DO UPDATE SET
+        block_num = EXCLUDED.block_num,
+        block_hash = EXCLUDED.block_hash,
+        extrinsic_index = EXCLUDED.extrinsic_index,
+        hash = EXCLUDED.hash,
+        is_signed = EXCLUDED.is_signed,
+        signer = EXCLUDED.signer,
+        nonce = EXCLUDED.nonce,
+        tip = EXCLUDED.tip,
+        era = EXCLUDED.era,
+        pallet = EXCLUDED.pallet,
+        call = EXCLUDED.call,
+        args = EXCLUDED.args,
+        raw = EXCLUDED.raw
+    "#;
+
+pub(crate) const EVENT_ON_CONFLICT: &str = r#"
+    ON CONFLICT (block_num, block_hash, event_index) DO UPDATE SET
+        block_num = EXCLUDED.block_num,
+        block_hash = EXCLUDED.block_hash,
+        event_index = EXCLUDED.event_index,
+        phase = EXCLUDED.phase,
+        extrinsic_index = EXCLUDED.extrinsic_index,
+        pallet = EXCLUDED.pallet,
+        variant = EXCLUDED.variant,
+        topics = EXCLUDED.topics,
+        fields = EXCLUDED.fields
+    "#;
+
+// The code is identified by its hash.
+pub(crate) const RUNTIME_CODE_ON_CONFLICT: &str = "ON CONFLICT (code_hash) DO NOTHING";
+
+pub(crate) const STORAGE_PREFIX_ON_CONFLICT: &str = r#"
+    ON CONFLICT (prefix) DO UPDATE SET
+        pallet = EXCLUDED.pallet,
+        item = EXCLUDED.item
+    "#;
+
+// The decoded arguments of a key never change.
+pub(crate) const STORAGE_KEY_ON_CONFLICT: &str = "ON CONFLICT (key) DO NOTHING";
 
 #[async_trait::async_trait]
 pub trait InsertModel: Send + Sized {
-    async fn insert(self, conn: &mut PoolConnection<Postgres>) -> Result<u64, SqlxError>;
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError>;
+}
+
+// Only one block of every number is canonical, the inserted blocks orphan the others.
+async fn orphan_siblings(conn: &mut PgConnection, block_nums: Vec<i64>) -> Result<u64, SqlxError> {
+    let rows_affected =
+        sqlx::query("UPDATE block SET canonical = false WHERE canonical AND block_num = ANY($1)")
+            .bind(block_nums)
+            .execute(conn)
+            .await?
+            .rows_affected();
+    Ok(rows_affected)
 }
 
 #[async_trait::async_trait]
 impl InsertModel for MetadataModel {
-    async fn insert(self, conn: &mut PoolConnection<Postgres>) -> Result<u64, SqlxError> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
         let query: Query<'_, Postgres, PgArguments> = sqlx::query(
             r#"
             INSERT INTO metadata VALUES ($1, $2, $3, $4)
@@ -47,11 +142,13 @@ impl InsertModel for MetadataModel {
 
 #[async_trait::async_trait]
 impl InsertModel for BlockModel {
-    async fn insert(self, conn: &mut PoolConnection<Postgres>) -> Result<u64, SqlxError> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        orphan_siblings(&mut *conn, vec![self.block_num.into()]).await?;
+
         let query: Query<'_, Postgres, PgArguments> = sqlx::query(
             r#"
             INSERT INTO block VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-            ON CONFLICT (block_num) DO UPDATE SET
+            ON CONFLICT (block_num, block_hash) DO UPDATE SET
                 version = EXCLUDED.version,
                 block_num = EXCLUDED.block_num,
                 block_hash = EXCLUDED.block_hash,
@@ -60,7 +157,8 @@ impl InsertModel for BlockModel {
                 extrinsics_root = EXCLUDED.extrinsics_root,
                 digest = EXCLUDED.digest,
                 extrinsics = EXCLUDED.extrinsics,
-                justifications = EXCLUDED.justifications
+                justifications = EXCLUDED.justifications,
+                canonical = EXCLUDED.canonical
             "#,
         )
         .bind(self.version)
@@ -90,7 +188,7 @@ impl InsertModel for BlockModel {
 
 #[async_trait::async_trait]
 impl InsertModel for Vec<BlockModel> {
-    async fn insert(self, conn: &mut PoolConnection<Postgres>) -> Result<u64, SqlxError> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
         log::debug!(
             target: "postgres",
             "Insert bulk block into postgres, height = [{:?}~{:?}]",
@@ -98,22 +196,14 @@ impl InsertModel for Vec<BlockModel> {
             self.last().map(|block| block.block_num)
         );
 
-        let mut batch = Batch::new(
-            "block",
-            "INSERT INTO block VALUES",
-            r#"
-            ON CONFLICT (block_num) DO UPDATE SET
-                version = EXCLUDED.version,
-                block_num = EXCLUDED.block_num,
-                block_hash = EXCLUDED.block_hash,
-                parent_hash = EXCLUDED.parent_hash,
-                state_root = EXCLUDED.state_root,
-                extrinsics_root = EXCLUDED.extrinsics_root,
-                digest = EXCLUDED.digest,
-                extrinsics = EXCLUDED.extrinsics,
-                justifications = EXCLUDED.justifications
-            "#,
-        );
+        let block_nums = self.iter().map(|block| block.block_num.into()).collect();
+        orphan_siblings(&mut *conn, block_nums).await?;
+
+        if self.len() >= COPY_THRESHOLD {
+            return self.copy(conn).await;
+        }
+
+        let mut batch = Batch::new("block", "INSERT INTO block VALUES", BLOCK_ON_CONFLICT);
         for model in self {
             batch.reserve(9)?;
             if batch.current_num_arguments() > 0 {
@@ -152,7 +242,7 @@ impl InsertModel for Vec<BlockModel> {
 
 #[async_trait::async_trait]
 impl InsertModel for MainStorageChangeModel {
-    async fn insert(self, conn: &mut PoolConnection<Postgres>) -> Result<u64, SqlxError> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
         log::debug!(
             target: "postgres",
             "Insert main storage into postgres, height = {}, key = 0x{}",
@@ -161,20 +251,22 @@ impl InsertModel for MainStorageChangeModel {
 
         let query: Query<'_, Postgres, PgArguments> = sqlx::query(
             r#"
-            INSERT INTO main_storage VALUES ($1, $2, $3, $4, $5)
-            ON CONFLICT (block_num, key) DO UPDATE SET
+            INSERT INTO main_storage VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (block_num, block_hash, key) DO UPDATE SET
                 block_num = EXCLUDED.block_num,
                 block_hash = EXCLUDED.block_hash,
                 prefix = EXCLUDED.prefix,
                 key = EXCLUDED.key,
-                data = EXCLUDED.data
+                data = EXCLUDED.data,
+                kind = EXCLUDED.kind
             "#,
         )
         .bind(self.block_num)
         .bind(self.block_hash)
         .bind(self.prefix)
         .bind(self.key)
-        .bind(self.data);
+        .bind(self.data)
+        .bind(self.kind);
 
         let rows_affected = query.execute(conn).await?.rows_affected();
         log::debug!(
@@ -188,11 +280,11 @@ impl InsertModel for MainStorageChangeModel {
 
 #[async_trait::async_trait]
 impl InsertModel for Vec<MainStorageChangeModel> {
-    async fn insert(self, conn: &mut PoolConnection<Postgres>) -> Result<u64, SqlxError> {
-        assert!(
-            !self.is_empty(),
-            "main storage changes not empty for each block"
-        );
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        // the blocks archived without execution don't have any main storage changes.
+        if self.is_empty() {
+            return Ok(0);
+        }
 
         log::debug!(
             target: "postgres",
@@ -201,47 +293,17 @@ impl InsertModel for Vec<MainStorageChangeModel> {
             self.last().map(|storage| storage.block_num)
         );
 
-        /*
-        let query: Query<'_, Postgres, PgArguments> = sqlx::query(
-            r#"
-            INSERT INTO main_storage VALUES ($1, $2, $3, $4, $5)
-            ON CONFLICT (block_num, key) DO UPDATE SET
-                block_num = EXCLUDED.block_num,
-                block_hash = EXCLUDED.block_hash,
-                prefix = EXCLUDED.prefix,
-                key = EXCLUDED.key,
-                data = EXCLUDED.data
-            "#,
-        );
-        use sqlx::prelude::*;
-        let mut tx = conn.begin().await?;
-        let mut rows_affected = 0;
-        for model in self {
-            let query: Query<'_, Postgres, PgArguments> = query
-                .bind(model.block_num)
-                .bind(model.block_hash)
-                .bind(model.prefix)
-                .bind(model.key)
-                .bind(model.data);
-            rows_affected += query.execute(&mut tx).await?.rows_affected();
+        if self.len() >= COPY_THRESHOLD {
+            return self.copy(conn).await;
         }
-        tx.commit().await?;
-        */
 
         let mut batch = Batch::new(
             "main_storage",
             "INSERT INTO main_storage VALUES",
-            r#"
-            ON CONFLICT (block_num, key) DO UPDATE SET
-                block_num = EXCLUDED.block_num,
-                block_hash = EXCLUDED.block_hash,
-                prefix = EXCLUDED.prefix,
-                key = EXCLUDED.key,
-                data = EXCLUDED.data
-            "#,
+            MAIN_STORAGE_ON_CONFLICT,
         );
         for model in self {
-            batch.reserve(5)?;
+            batch.reserve(6)?;
             if batch.current_num_arguments() > 0 {
                 batch.append(",");
             }
@@ -255,6 +317,8 @@ impl InsertModel for Vec<MainStorageChangeModel> {
             batch.bind(model.key)?;
             batch.append(",");
             batch.bind(model.data)?;
+            batch.append(",");
+            batch.bind(model.kind)?;
             batch.append(")");
         }
         let rows_affected = batch.execute(conn).await?;
@@ -270,7 +334,7 @@ impl InsertModel for Vec<MainStorageChangeModel> {
 
 #[async_trait::async_trait]
 impl InsertModel for ChildStorageChangeModel {
-    async fn insert(self, conn: &mut PoolConnection<Postgres>) -> Result<u64, SqlxError> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
         log::debug!(
             target: "postgres",
             "Insert child storage into postgres, height = {}, prefix key = 0x{},  key = 0x{}",
@@ -279,8 +343,8 @@ impl InsertModel for ChildStorageChangeModel {
 
         let query: Query<'_, Postgres, PgArguments> = sqlx::query(
             r#"
-            INSERT INTO chlid_storage VALUES ($1, $2, $3, $4, $5)
-            ON CONFLICT (block_num, prefix_key, key) DO UPDATE SET
+            INSERT INTO child_storage VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (block_num, block_hash, prefix_key, key) DO UPDATE SET
                 block_num = EXCLUDED.block_num,
                 block_hash = EXCLUDED.block_hash,
                 prefix_key = EXCLUDED.prefix_key,
@@ -306,14 +370,343 @@ impl InsertModel for ChildStorageChangeModel {
 
 #[async_trait::async_trait]
 impl InsertModel for Vec<ChildStorageChangeModel> {
-    async fn insert(self, _conn: &mut PoolConnection<Postgres>) -> Result<u64, SqlxError> {
-        todo!()
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        // most of blocks don't have any child storage changes.
+        if self.is_empty() {
+            return Ok(0);
+        }
+
+        log::debug!(
+            target: "postgres",
+            "Insert bulk child storage into postgres, height = [{:?}~{:?}]",
+            self.first().map(|storage| storage.block_num),
+            self.last().map(|storage| storage.block_num)
+        );
+
+        if self.len() >= COPY_THRESHOLD {
+            return self.copy(conn).await;
+        }
+
+        let mut batch = Batch::new(
+            "child_storage",
+            "INSERT INTO child_storage VALUES",
+            CHILD_STORAGE_ON_CONFLICT,
+        );
+        for model in self {
+            batch.reserve(5)?;
+            if batch.current_num_arguments() > 0 {
+                batch.append(",");
+            }
+            batch.append("(");
+            batch.bind(model.block_num)?;
+            batch.append(",");
+            batch.bind(model.block_hash)?;
+            batch.append(",");
+            batch.bind(model.prefix_key)?;
+            batch.append(",");
+            batch.bind(model.key)?;
+            batch.append(",");
+            batch.bind(model.data)?;
+            batch.append(")");
+        }
+        let rows_affected = batch.execute(conn).await?;
+
+        log::debug!(
+            target: "postgres",
+            "Insert bulk child storage into postgres, affected rows = {}",
+            rows_affected
+        );
+        Ok(rows_affected)
+    }
+}
+
+#[async_trait::async_trait]
+impl InsertModel for Vec<ExtrinsicModel> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        if self.is_empty() {
+            return Ok(0);
+        }
+
+        log::debug!(
+            target: "postgres",
+            "Insert bulk extrinsic into postgres, height = [{:?}~{:?}]",
+            self.first().map(|extrinsic| extrinsic.block_num),
+            self.last().map(|extrinsic| extrinsic.block_num)
+        );
+
+        let mut batch = Batch::new(
+            "extrinsic",
+            "INSERT INTO extrinsic VALUES",
+            EXTRINSIC_ON_CONFLICT,
+        );
+        for model in self {
+            batch.reserve(13)?;
+            if batch.current_num_arguments() > 0 {
+                batch.append(",");
+            }
+            batch.append("(");
+            batch.bind(model.block_num)?;
+            batch.append(",");
+            batch.bind(model.block_hash)?;
+            batch.append(",");
+            batch.bind(model.extrinsic_index)?;
+            batch.append(",");
+            batch.bind(model.hash)?;
+            batch.append(",");
+            batch.bind(model.is_signed)?;
+            batch.append(",");
+            batch.bind(model.signer)?;
+            batch.append(",");
+            batch.bind(model.nonce)?;
+            batch.append(",");
+            batch.bind(model.tip)?;
+            batch.append("::numeric,");
+            batch.bind(model.era)?;
+            batch.append(",");
+            batch.bind(model.pallet)?;
+            batch.append(",");
+            batch.bind(model.call)?;
+            batch.append(",");
+            batch.bind(model.args)?;
+            batch.append(",");
+            batch.bind(model.raw)?;
+            batch.append(")");
+        }
+        let rows_affected = batch.execute(conn).await?;
+
+        log::debug!(
+            target: "postgres",
+            "Insert bulk extrinsic into postgres, affected rows = {}",
+            rows_affected
+        );
+        Ok(rows_affected)
+    }
+}
+
+#[async_trait::async_trait]
+impl InsertModel for Vec<EventModel> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        // the events can't be decoded without the metadata v14.
+        if self.is_empty() {
+            return Ok(0);
+        }
+
+        log::debug!(
+            target: "postgres",
+            "Insert bulk event into postgres, height = [{:?}~{:?}]",
+            self.first().map(|event| event.block_num),
+            self.last().map(|event| event.block_num)
+        );
+
+        let mut batch = Batch::new("event", "INSERT INTO event VALUES", EVENT_ON_CONFLICT);
+        for model in self {
+            batch.reserve(9)?;
+            if batch.current_num_arguments() > 0 {
+                batch.append(",");
+            }
+            batch.append("(");
+            batch.bind(model.block_num)?;
+            batch.append(",");
+            batch.bind(model.block_hash)?;
+            batch.append(",");
+            batch.bind(model.event_index)?;
+            batch.append(",");
+            batch.bind(model.phase)?;
+            batch.append(",");
+            batch.bind(model.extrinsic_index)?;
+            batch.append(",");
+            batch.bind(model.pallet)?;
+            batch.append(",");
+            batch.bind(model.variant)?;
+            batch.append(",");
+            batch.bind(model.topics)?;
+            batch.append(",");
+            batch.bind(model.fields)?;
+            batch.append(")");
+        }
+        let rows_affected = batch.execute(conn).await?;
+
+        log::debug!(
+            target: "postgres",
+            "Insert bulk event into postgres, affected rows = {}",
+            rows_affected
+        );
+        Ok(rows_affected)
+    }
+}
+
+#[async_trait::async_trait]
+impl InsertModel for Vec<RuntimeCodeModel> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        // the code is only sent when it's changed.
+        if self.is_empty() {
+            return Ok(0);
+        }
+
+        let mut batch = Batch::new(
+            "runtime_code",
+            "INSERT INTO runtime_code VALUES",
+            RUNTIME_CODE_ON_CONFLICT,
+        );
+        for model in self {
+            batch.reserve(2)?;
+            if batch.current_num_arguments() > 0 {
+                batch.append(",");
+            }
+            batch.append("(");
+            batch.bind(model.code_hash)?;
+            batch.append(",");
+            batch.bind(model.code)?;
+            batch.append(")");
+        }
+        let rows_affected = batch.execute(conn).await?;
+
+        log::debug!(
+            target: "postgres",
+            "Insert bulk runtime code into postgres, affected rows = {}",
+            rows_affected
+        );
+        Ok(rows_affected)
+    }
+}
+
+// The blocks may be archived out of order, so the range of the runtime is merged with the
+// adjacent or overlapped ranges of the same code. The ranges separated by another code are kept
+// apart, so that a code active again after an upgrade (A -> B -> A) starts a new range.
+#[async_trait::async_trait]
+impl InsertModel for RuntimeVersionModel {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        let merged: Vec<(i32, i32)> = sqlx::query_as(
+            r#"
+            DELETE FROM runtime_version
+            WHERE code_hash = $1 AND first_block <= $3 + 1 AND last_block >= $2 - 1
+            RETURNING first_block, last_block
+            "#,
+        )
+        .bind(&self.code_hash)
+        .bind(self.first_block as i64)
+        .bind(self.last_block as i64)
+        .fetch_all(&mut *conn)
+        .await?;
+        let first_block = merged
+            .iter()
+            .fold(self.first_block, |first, range| first.min(range.0 as u32));
+        let last_block = merged
+            .iter()
+            .fold(self.last_block, |last, range| last.max(range.1 as u32));
+
+        let query: Query<'_, Postgres, PgArguments> = sqlx::query(
+            "INSERT INTO runtime_version VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+        )
+        .bind(self.code_hash)
+        .bind(self.spec_name)
+        .bind(self.impl_name)
+        .bind(self.authoring_version)
+        .bind(self.spec_version)
+        .bind(self.impl_version)
+        .bind(self.transaction_version)
+        .bind(self.apis)
+        .bind(first_block)
+        .bind(last_block);
+
+        let rows_affected = query.execute(conn).await?.rows_affected();
+        Ok(rows_affected)
+    }
+}
+
+#[async_trait::async_trait]
+impl InsertModel for Vec<RuntimeVersionModel> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        let mut rows_affected = 0;
+        for model in self {
+            rows_affected += model.insert(&mut *conn).await?;
+        }
+
+        log::debug!(
+            target: "postgres",
+            "Insert bulk runtime version into postgres, affected rows = {}",
+            rows_affected
+        );
+        Ok(rows_affected)
+    }
+}
+
+#[async_trait::async_trait]
+impl InsertModel for Vec<StoragePrefixModel> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        // the prefixes are only inserted once for every metadata version.
+        if self.is_empty() {
+            return Ok(0);
+        }
+
+        let mut batch = Batch::new(
+            "storage_prefix",
+            "INSERT INTO storage_prefix VALUES",
+            STORAGE_PREFIX_ON_CONFLICT,
+        );
+        for model in self {
+            batch.reserve(3)?;
+            if batch.current_num_arguments() > 0 {
+                batch.append(",");
+            }
+            batch.append("(");
+            batch.bind(model.prefix)?;
+            batch.append(",");
+            batch.bind(model.pallet)?;
+            batch.append(",");
+            batch.bind(model.item)?;
+            batch.append(")");
+        }
+        let rows_affected = batch.execute(conn).await?;
+
+        log::debug!(
+            target: "postgres",
+            "Insert bulk storage prefix into postgres, affected rows = {}",
+            rows_affected
+        );
+        Ok(rows_affected)
+    }
+}
+
+#[async_trait::async_trait]
+impl InsertModel for Vec<StorageKeyModel> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        if self.is_empty() {
+            return Ok(0);
+        }
+
+        let mut batch = Batch::new(
+            "storage_key",
+            "INSERT INTO storage_key VALUES",
+            STORAGE_KEY_ON_CONFLICT,
+        );
+        for model in self {
+            batch.reserve(3)?;
+            if batch.current_num_arguments() > 0 {
+                batch.append(",");
+            }
+            batch.append("(");
+            batch.bind(model.key)?;
+            batch.append(",");
+            batch.bind(model.prefix)?;
+            batch.append(",");
+            batch.bind(model.args)?;
+            batch.append(")");
+        }
+        let rows_affected = batch.execute(conn).await?;
+
+        log::debug!(
+            target: "postgres",
+            "Insert bulk storage key into postgres, affected rows = {}",
+            rows_affected
+        );
+        Ok(rows_affected)
     }
 }
 
 #[async_trait::async_trait]
 impl InsertModel for BestBlockModel {
-    async fn insert(self, conn: &mut PoolConnection<Postgres>) -> Result<u64, SqlxError> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
         log::info!(
             target: "postgres",
             "Update best block #{} (0x{})",
@@ -345,7 +738,7 @@ impl InsertModel for BestBlockModel {
 
 #[async_trait::async_trait]
 impl InsertModel for FinalizedBlockModel {
-    async fn insert(self, conn: &mut PoolConnection<Postgres>) -> Result<u64, SqlxError> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
         log::info!(
             target: "postgres",
             "Update finalized block #{} (0x{})",
@@ -374,3 +767,105 @@ impl InsertModel for FinalizedBlockModel {
         Ok(rows_affected)
     }
 }
+
+#[async_trait::async_trait]
+impl InsertModel for FailedBlockModel {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        log::warn!(
+            target: "postgres",
+            "Record failed block #{} after {} attempts: {}",
+            self.block_num,
+            self.attempts,
+            self.error
+        );
+
+        let query: Query<'_, Postgres, PgArguments> = sqlx::query(
+            r#"
+            INSERT INTO failed_block (block_num, attempts, error) VALUES ($1, $2, $3)
+            ON CONFLICT (block_num) DO UPDATE SET
+                attempts = failed_block.attempts + EXCLUDED.attempts,
+                error = EXCLUDED.error,
+                failed_at = now()
+            "#,
+        )
+        .bind(self.block_num)
+        .bind(self.attempts)
+        .bind(self.error);
+
+        let rows_affected = query.execute(conn).await?.rows_affected();
+        Ok(rows_affected)
+    }
+}
+
+#[async_trait::async_trait]
+impl InsertModel for Vec<OutboxModel> {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        if self.is_empty() {
+            return Ok(0);
+        }
+
+        // the ids are assigned in the order of rows.
+        let mut batch = Batch::new(
+            "outbox",
+            "INSERT INTO outbox (kind, key, payload) VALUES",
+            "",
+        );
+        for model in self {
+            batch.reserve(3)?;
+            if batch.current_num_arguments() > 0 {
+                batch.append(",");
+            }
+            batch.append("(");
+            batch.bind(model.kind)?;
+            batch.append(",");
+            batch.bind(model.key)?;
+            batch.append(",");
+            batch.bind(model.payload)?;
+            batch.append(")");
+        }
+        let rows_affected = batch.execute(conn).await?;
+
+        log::debug!(
+            target: "postgres",
+            "Insert bulk outbox into postgres, affected rows = {}",
+            rows_affected
+        );
+        Ok(rows_affected)
+    }
+}
+
+/// Insert the offsets of the new sinks, which start from the oldest outbox row that is retained,
+/// the offsets of the registered sinks are unchanged.
+pub(crate) async fn register_outbox_sinks(
+    conn: &mut PgConnection,
+    sinks: Vec<String>,
+) -> Result<u64, SqlxError> {
+    let rows_affected = sqlx::query(
+        r#"
+        INSERT INTO outbox_offset (sink, last_id) SELECT UNNEST($1::text[]), 0
+        ON CONFLICT (sink) DO NOTHING
+        "#,
+    )
+    .bind(sinks)
+    .execute(conn)
+    .await?
+    .rows_affected();
+    Ok(rows_affected)
+}
+
+#[async_trait::async_trait]
+impl InsertModel for OutboxOffsetModel {
+    async fn insert(self, conn: &mut PgConnection) -> Result<u64, SqlxError> {
+        let query: Query<'_, Postgres, PgArguments> = sqlx::query(
+            r#"
+            INSERT INTO outbox_offset (sink, last_id) VALUES ($1, $2)
+            ON CONFLICT (sink) DO UPDATE SET last_id = EXCLUDED.last_id
+            "#,
+        )
+        .bind(self.sink)
+        .bind(self.last_id);
+
+        let rows_affected = query.execute(conn).await?.rows_affected();
+        Ok(rows_affected)
+    }
+}
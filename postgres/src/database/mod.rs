@@ -1,28 +1,43 @@
 #[allow(unused)]
 mod batch;
+mod copy;
 mod delete;
 mod insert;
+mod partition;
 pub mod query;
 
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use sqlx::{
     error::Error as SqlxError,
     pool::PoolConnection,
     postgres::{PgConnectOptions, PgPool, PgPoolOptions, Postgres},
-    ConnectOptions,
+    ConnectOptions, Transaction,
+};
+
+pub use self::{
+    copy::{CopyModel, COPY_THRESHOLD},
+    partition::DEFAULT_PARTITION_WIDTH,
 };
 
 use self::{delete::DeleteModel, insert::InsertModel};
 use crate::{
     config::PostgresConfig,
-    model::{BlockModel, MainStorageChangeModel, MetadataModel},
+    model::{OutboxModel, OutboxOffsetModel, RuntimeCodeModel, RuntimeVersionModel},
 };
 
 #[derive(Clone)]
 pub struct PostgresDb {
     config: PostgresConfig,
     pool: PgPool,
+    // The upper bound (exclusive) of block_num that all partitioned tables can hold.
+    partitioned_until: Arc<AtomicU32>,
 }
 
 impl PostgresDb {
@@ -43,7 +58,11 @@ impl PostgresDb {
             .connect_with(options)
             .await?;
         log::info!(target: "postgres", "Postgres configuration: {:?}", config);
-        Ok(Self { config, pool })
+        Ok(Self {
+            config,
+            pool,
+            partitioned_until: Arc::new(AtomicU32::new(0)),
+        })
     }
 
     pub fn config(&self) -> &PostgresConfig {
@@ -64,13 +83,53 @@ impl PostgresDb {
         Ok(rows_affected)
     }
 
-    /// Delete models where block_num > `block_num`
-    pub async fn delete(&self, block_num: u32) -> Result<u64, SqlxError> {
+    /// Bulk load models with `COPY`, regardless of the number of rows.
+    pub async fn copy(&self, model: impl CopyModel) -> Result<u64, SqlxError> {
         let mut conn = self.conn().await?;
-        let rows1 = MainStorageChangeModel::delete(&mut conn, block_num).await?;
-        let rows2 = BlockModel::delete(&mut conn, block_num).await?;
-        let rows3 = MetadataModel::delete(&mut conn, block_num).await?;
-        Ok(rows1 + rows2 + rows3)
+        let rows_affected = model.copy(&mut conn).await?;
+        Ok(rows_affected)
+    }
+
+    /// Begin a transaction, the models written by the transaction are committed all-or-nothing.
+    pub async fn begin(&self) -> Result<PostgresTransaction, SqlxError> {
+        let tx = self.pool.begin().await?;
+        Ok(PostgresTransaction { tx })
+    }
+
+    /// Rollback to the block, the blocks (block_num > `block_num`) become non-canonical.
+    /// Returns the numbers and hashes of the orphaned blocks, in descending order of the number.
+    ///
+    /// The rows derived from the orphaned blocks are kept, the `canonical_*` views only contain
+    /// the rows of the canonical chain.
+    pub async fn orphan_blocks(&self, block_num: u32) -> Result<Vec<(u32, Vec<u8>)>, SqlxError> {
+        let mut tx = self.begin().await?;
+        let orphaned = tx.orphan_blocks(block_num).await?;
+        tx.commit().await?;
+        Ok(orphaned)
+    }
+
+    /// Make sure that all tables partitioned by range can hold the blocks up to
+    /// `block_num + partition_width`, create the missing partitions if necessary.
+    pub async fn ensure_partitions(&self, block_num: u32) -> Result<(), SqlxError> {
+        let width = self.config.partition_width();
+        let until = block_num.saturating_add(width);
+        if until < self.partitioned_until.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        let mut conn = self.conn().await?;
+        let mut partitioned_until = u32::MAX;
+        for (table, upper) in partition::partition_upper_bounds(&mut conn).await? {
+            let upper = if upper <= until {
+                partition::create_partitions(&mut conn, &table, upper, until, width).await?
+            } else {
+                upper
+            };
+            partitioned_until = partitioned_until.min(upper);
+        }
+        self.partitioned_until
+            .store(partitioned_until, Ordering::Release);
+        Ok(())
     }
 
     pub async fn if_metadata_exists(&self, version: u32) -> Result<bool, SqlxError> {
@@ -79,6 +138,18 @@ impl PostgresDb {
         Ok(does_exist)
     }
 
+    pub async fn metadata_versions(&self) -> Result<Vec<u32>, SqlxError> {
+        let mut conn = self.conn().await?;
+        let versions = query::get_all_metadata_versions(&mut conn).await?;
+        Ok(versions)
+    }
+
+    pub async fn metadata(&self, version: u32) -> Result<Option<Vec<u8>>, SqlxError> {
+        let mut conn = self.conn().await?;
+        let metadata = query::get_metadata(version, &mut conn).await?;
+        Ok(metadata)
+    }
+
     pub async fn max_block_num(&self) -> Result<Option<u32>, SqlxError> {
         let mut conn = self.conn().await?;
         let max = query::max_block_num(&mut conn).await?;
@@ -96,4 +167,112 @@ impl PostgresDb {
         let finalized_block = query::finalized_block_num(&mut conn).await?;
         Ok(finalized_block)
     }
+
+    pub async fn block_hashes(&self, from: u32, to: u32) -> Result<Vec<(u32, Vec<u8>)>, SqlxError> {
+        let mut conn = self.conn().await?;
+        let blocks = query::block_hashes(from, to, &mut conn).await?;
+        Ok(blocks)
+    }
+
+    pub async fn block_gaps(&self, from: u32, to: u32) -> Result<Vec<(u32, u32)>, SqlxError> {
+        let mut conn = self.conn().await?;
+        let gaps = query::block_gaps(from, to, &mut conn).await?;
+        Ok(gaps)
+    }
+
+    pub async fn blocks_without_main_storage(
+        &self,
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<u32>, SqlxError> {
+        let mut conn = self.conn().await?;
+        let blocks = query::blocks_without_main_storage(from, to, &mut conn).await?;
+        Ok(blocks)
+    }
+
+    pub async fn outbox_offset(&self, sink: &str) -> Result<i64, SqlxError> {
+        let mut conn = self.conn().await?;
+        let offset = query::outbox_offset(sink, &mut conn).await?;
+        Ok(offset)
+    }
+
+    pub async fn outbox_after(
+        &self,
+        after: i64,
+        limit: i64,
+    ) -> Result<Vec<(i64, OutboxModel)>, SqlxError> {
+        let mut conn = self.conn().await?;
+        let rows = query::outbox_after(after, limit, &mut conn).await?;
+        Ok(rows)
+    }
+
+    /// Register the offsets of the configured sinks, and remove the offsets of the sinks that are
+    /// no longer configured, it must be called before any offset is committed, otherwise the rows
+    /// that a sink registered later hasn't published may be pruned.
+    ///
+    /// A new sink starts from the oldest outbox row that is retained, the rows pruned before it is
+    /// registered are never published to it, backfill the blocks to publish them again.
+    pub async fn register_outbox_sinks(&self, sinks: &[&str]) -> Result<(), SqlxError> {
+        let sinks = sinks
+            .iter()
+            .map(|sink| sink.to_string())
+            .collect::<Vec<_>>();
+        let mut tx = self.begin().await?;
+        delete::unregister_outbox_sinks(&mut tx.tx, sinks.clone()).await?;
+        let registered = insert::register_outbox_sinks(&mut tx.tx, sinks).await?;
+        delete::prune_outbox(&mut tx.tx).await?;
+        tx.commit().await?;
+        log::info!(target: "postgres", "Register {} new outbox sinks", registered);
+        Ok(())
+    }
+
+    /// Commit the offset of the sink, and remove the outbox rows that have been published by all sinks.
+    pub async fn commit_outbox_offset(&self, sink: &str, last_id: i64) -> Result<(), SqlxError> {
+        let mut tx = self.begin().await?;
+        tx.insert(OutboxOffsetModel {
+            sink: sink.to_string(),
+            last_id,
+        })
+        .await?;
+        delete::prune_outbox(&mut tx.tx).await?;
+        tx.commit().await
+    }
+}
+
+/// A postgres transaction, nothing written by it is visible until `commit` is called,
+/// and all of them are discarded if the transaction is dropped without `commit`.
+pub struct PostgresTransaction {
+    tx: Transaction<'static, Postgres>,
+}
+
+impl PostgresTransaction {
+    pub async fn insert(&mut self, model: impl InsertModel) -> Result<u64, SqlxError> {
+        let rows_affected = model.insert(&mut self.tx).await?;
+        Ok(rows_affected)
+    }
+
+    /// Mark the blocks (block_num > `block_num`) as non-canonical instead of deleting them.
+    pub async fn orphan_blocks(
+        &mut self,
+        block_num: u32,
+    ) -> Result<Vec<(u32, Vec<u8>)>, SqlxError> {
+        let orphaned = delete::orphan_blocks(&mut self.tx, block_num).await?;
+        // the runtime versions are derived from the canonical blocks.
+        RuntimeVersionModel::delete(&mut self.tx, block_num).await?;
+        RuntimeCodeModel::delete(&mut self.tx, block_num).await?;
+        Ok(orphaned)
+    }
+
+    /// Remove the failure records of the blocks, it's called along with inserting the blocks.
+    pub async fn clear_failed_blocks(&mut self, block_nums: Vec<u32>) -> Result<u64, SqlxError> {
+        delete::clear_failed_blocks(&mut self.tx, block_nums).await
+    }
+
+    pub async fn commit(self) -> Result<(), SqlxError> {
+        self.tx.commit().await
+    }
+
+    pub async fn rollback(self) -> Result<(), SqlxError> {
+        self.tx.rollback().await
+    }
 }
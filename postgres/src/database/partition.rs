@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use sqlx::{error::Error as SqlxError, pool::PoolConnection, postgres::Postgres, FromRow};
+
+/// The default number of blocks that a partition contains.
+pub const DEFAULT_PARTITION_WIDTH: u32 = 1_000_000;
+
+#[derive(Clone, Debug, Eq, PartialEq, FromRow)]
+struct PartitionBound {
+    table_name: String,
+    bound: Option<String>,
+}
+
+/// Returns the upper bound of the range partitions of every table partitioned by range,
+/// the upper bound of the table that has no partition yet is `0`.
+pub async fn partition_upper_bounds(
+    conn: &mut PoolConnection<Postgres>,
+) -> Result<HashMap<String, u32>, SqlxError> {
+    let bounds: Vec<PartitionBound> = sqlx::query_as(
+        r#"
+        SELECT parent.relname::text AS table_name, pg_get_expr(child.relpartbound, child.oid) AS bound
+        FROM pg_partitioned_table AS pt
+        JOIN pg_class AS parent ON parent.oid = pt.partrelid
+        LEFT JOIN pg_inherits AS inherits ON inherits.inhparent = parent.oid
+        LEFT JOIN pg_class AS child ON child.oid = inherits.inhrelid
+        WHERE pt.partstrat = 'r'
+            AND NOT parent.relispartition
+            AND parent.relnamespace = current_schema()::regnamespace
+        "#,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut upper_bounds = HashMap::new();
+    for PartitionBound { table_name, bound } in bounds {
+        let upper = bound
+            .as_deref()
+            .and_then(parse_upper_bound)
+            .unwrap_or_default();
+        let entry = upper_bounds.entry(table_name).or_insert(0);
+        *entry = (*entry).max(upper);
+    }
+    Ok(upper_bounds)
+}
+
+/// Parses the upper bound from the partition bound expression,
+/// e.g. `FOR VALUES FROM (0) TO (1000000)` => `1000000`.
+fn parse_upper_bound(bound: &str) -> Option<u32> {
+    let (_, upper) = bound.split_once("TO (")?;
+    let (upper, _) = upper.split_once(')')?;
+    upper.trim().trim_matches('\'').parse().ok()
+}
+
+/// Creates the partitions of `table` from the block `from` until the block `until` is covered,
+/// each partition contains `width` blocks. Returns the new upper bound of the partitions.
+pub async fn create_partitions(
+    conn: &mut PoolConnection<Postgres>,
+    table: &str,
+    from: u32,
+    until: u32,
+    width: u32,
+) -> Result<u32, SqlxError> {
+    let mut start = from;
+    while start <= until {
+        let end = start.saturating_add(width);
+        let sql = format!(
+            r#"CREATE TABLE IF NOT EXISTS "{table}_{start}_{end}" PARTITION OF "{table}" FOR VALUES FROM ({start}) TO ({end})"#,
+            table = table,
+            start = start,
+            end = end,
+        );
+        sqlx::query(&sql).execute(&mut *conn).await?;
+        log::info!(
+            target: "postgres",
+            "Create partition of {} for block_num [{}, {})",
+            table, start, end
+        );
+        if end == start {
+            break;
+        }
+        start = end;
+    }
+    Ok(start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_partition_upper_bound() {
+        assert_eq!(
+            parse_upper_bound("FOR VALUES FROM (0) TO (1000000)"),
+            Some(1_000_000)
+        );
+        assert_eq!(
+            parse_upper_bound("FOR VALUES FROM ('19000000') TO ('20000000')"),
+            Some(20_000_000)
+        );
+        assert_eq!(parse_upper_bound("DEFAULT"), None);
+    }
+}
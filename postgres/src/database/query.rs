@@ -4,6 +4,8 @@ use sqlx::{
     Arguments, Error as SqlxError, FromRow,
 };
 
+use crate::model::OutboxModel;
+
 pub async fn check_if_metadata_exists(
     version: u32,
     conn: &mut PoolConnection<Postgres>,
@@ -43,6 +45,24 @@ pub async fn get_all_metadata_versions(
         .collect())
 }
 
+pub async fn get_metadata(
+    version: u32,
+    conn: &mut PoolConnection<Postgres>,
+) -> Result<Option<Vec<u8>>, SqlxError> {
+    #[derive(Clone, Debug, Eq, PartialEq, FromRow)]
+    struct Metadata {
+        metadata: Vec<u8>,
+    }
+
+    let mut args = PgArguments::default();
+    args.add(version);
+    let metadata: Option<Metadata> =
+        sqlx::query_as_with(r#"SELECT metadata FROM metadata WHERE version = $1"#, args)
+            .fetch_optional(conn)
+            .await?;
+    Ok(metadata.map(|metadata| metadata.metadata))
+}
+
 pub async fn max_block_num(conn: &mut PoolConnection<Postgres>) -> Result<Option<u32>, SqlxError> {
     /// Return type of queries that `SELECT MAX(int)`
     #[derive(Copy, Clone, Debug, Eq, PartialEq, FromRow)]
@@ -50,7 +70,7 @@ pub async fn max_block_num(conn: &mut PoolConnection<Postgres>) -> Result<Option
         max: Option<i32>,
     }
 
-    let max: Max = sqlx::query_as(r#"SELECT MAX(block_num) FROM block"#)
+    let max: Max = sqlx::query_as(r#"SELECT MAX(block_num) FROM block WHERE canonical"#)
         .fetch_one(conn)
         .await?;
     Ok(max.max.map(|v| v as u32))
@@ -81,3 +101,146 @@ pub async fn finalized_block_num(
             .await?;
     Ok(best_block.map(|block| (block.block_num as u32, block.block_hash)))
 }
+
+/// Returns the numbers and hashes of the canonical blocks within `[from, to]`, in descending order of the number.
+pub async fn block_hashes(
+    from: u32,
+    to: u32,
+    conn: &mut PoolConnection<Postgres>,
+) -> Result<Vec<(u32, Vec<u8>)>, SqlxError> {
+    let blocks: Vec<BlockForQuery> = sqlx::query_as(
+        r#"SELECT block_num, block_hash FROM block WHERE canonical AND block_num BETWEEN $1 AND $2 ORDER BY block_num DESC"#,
+    )
+    .bind(from as i64)
+    .bind(to as i64)
+    .fetch_all(conn)
+    .await?;
+    Ok(blocks
+        .into_iter()
+        .map(|block| (block.block_num as u32, block.block_hash))
+        .collect())
+}
+
+/// Returns the ranges `[start, end]` of the block numbers within `[from, to]` that have no canonical block.
+pub async fn block_gaps(
+    from: u32,
+    to: u32,
+    conn: &mut PoolConnection<Postgres>,
+) -> Result<Vec<(u32, u32)>, SqlxError> {
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, FromRow)]
+    struct BlockGap {
+        gap_start: i64,
+        gap_end: i64,
+    }
+
+    // `from - 1` and `to + 1` are the sentinels for finding the gaps at both ends of the range.
+    let gaps: Vec<BlockGap> = sqlx::query_as(
+        r#"
+        SELECT block_num + 1 AS gap_start, next_block_num - 1 AS gap_end
+        FROM (
+            SELECT block_num, LEAD(block_num) OVER (ORDER BY block_num) AS next_block_num
+            FROM (
+                SELECT block_num::bigint FROM block WHERE canonical AND block_num BETWEEN $1 AND $2
+                UNION ALL SELECT $1 - 1
+                UNION ALL SELECT $2 + 1
+            ) AS blocks
+        ) AS neighbors
+        WHERE next_block_num > block_num + 1
+        ORDER BY block_num
+        "#,
+    )
+    .bind(from as i64)
+    .bind(to as i64)
+    .fetch_all(conn)
+    .await?;
+    Ok(gaps
+        .into_iter()
+        .map(|gap| (gap.gap_start as u32, gap.gap_end as u32))
+        .collect())
+}
+
+/// Returns the numbers of the canonical blocks within `[from, to]` that have no main storage change.
+pub async fn blocks_without_main_storage(
+    from: u32,
+    to: u32,
+    conn: &mut PoolConnection<Postgres>,
+) -> Result<Vec<u32>, SqlxError> {
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, FromRow)]
+    struct BlockNum {
+        block_num: i32,
+    }
+
+    let blocks: Vec<BlockNum> = sqlx::query_as(
+        r#"
+        SELECT block_num FROM block
+        WHERE canonical AND block_num BETWEEN $1 AND $2
+            AND NOT EXISTS (
+                SELECT 1 FROM main_storage
+                WHERE main_storage.block_num = block.block_num AND main_storage.block_hash = block.block_hash
+            )
+        ORDER BY block_num
+        "#,
+    )
+    .bind(from as i64)
+    .bind(to as i64)
+    .fetch_all(conn)
+    .await?;
+    Ok(blocks
+        .into_iter()
+        .map(|block| block.block_num as u32)
+        .collect())
+}
+
+/// Returns the id of the last outbox row that the sink has published, `0` if the sink is new.
+pub async fn outbox_offset(
+    sink: &str,
+    conn: &mut PoolConnection<Postgres>,
+) -> Result<i64, SqlxError> {
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, FromRow)]
+    struct Offset {
+        last_id: i64,
+    }
+
+    let offset: Option<Offset> =
+        sqlx::query_as(r#"SELECT last_id FROM outbox_offset WHERE sink = $1"#)
+            .bind(sink)
+            .fetch_optional(conn)
+            .await?;
+    Ok(offset.map(|offset| offset.last_id).unwrap_or_default())
+}
+
+/// Returns at most `limit` outbox rows after the id, in ascending order of the id.
+pub async fn outbox_after(
+    after: i64,
+    limit: i64,
+    conn: &mut PoolConnection<Postgres>,
+) -> Result<Vec<(i64, OutboxModel)>, SqlxError> {
+    #[derive(Clone, Debug, Eq, PartialEq, FromRow)]
+    struct Outbox {
+        id: i64,
+        kind: String,
+        key: Option<String>,
+        payload: String,
+    }
+
+    let rows: Vec<Outbox> = sqlx::query_as(
+        r#"SELECT id, kind, key, payload FROM outbox WHERE id > $1 ORDER BY id LIMIT $2"#,
+    )
+    .bind(after)
+    .bind(limit)
+    .fetch_all(conn)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            (
+                row.id,
+                OutboxModel {
+                    kind: row.kind,
+                    key: row.key,
+                    payload: row.payload,
+                },
+            )
+        })
+        .collect())
+}
@@ -4,7 +4,7 @@ pub mod model;
 
 pub use self::{
     config::PostgresConfig,
-    database::{query, PostgresDb},
+    database::{query, PostgresDb, PostgresTransaction, COPY_THRESHOLD},
     model::*,
 };
 pub use sqlx::error::Error as SqlxError;
@@ -30,6 +30,8 @@ pub struct MainStorageChangeModel {
     pub prefix: Vec<u8>,
     pub key: Vec<u8>,
     pub data: Option<Vec<u8>>,
+    // `well_known`, `pallet_value`, `pallet_map`, `child_trie_root` or `unknown`.
+    pub kind: String,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromRow)]
@@ -41,6 +43,71 @@ pub struct ChildStorageChangeModel {
     pub data: Option<Vec<u8>>,
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct ExtrinsicModel {
+    pub block_num: u32,
+    pub block_hash: Vec<u8>,
+    pub extrinsic_index: u32,
+    pub hash: Vec<u8>,
+    pub is_signed: bool,
+    pub signer: Option<Vec<u8>>,
+    pub nonce: Option<u32>,
+    // The decimal string of the tip, it's stored as `numeric`.
+    pub tip: Option<String>,
+    pub era: Option<serde_json::Value>,
+    pub pallet: Option<String>,
+    pub call: Option<String>,
+    pub args: Option<serde_json::Value>,
+    pub raw: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct EventModel {
+    pub block_num: u32,
+    pub block_hash: Vec<u8>,
+    pub event_index: u32,
+    pub phase: String,
+    pub extrinsic_index: Option<u32>,
+    pub pallet: String,
+    pub variant: String,
+    pub topics: Vec<Vec<u8>>,
+    pub fields: serde_json::Value,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct RuntimeCodeModel {
+    pub code_hash: Vec<u8>,
+    pub code: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct RuntimeVersionModel {
+    pub code_hash: Vec<u8>,
+    pub spec_name: String,
+    pub impl_name: String,
+    pub authoring_version: u32,
+    pub spec_version: u32,
+    pub impl_version: u32,
+    pub transaction_version: u32,
+    pub apis: serde_json::Value,
+    pub first_block: u32,
+    pub last_block: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct StoragePrefixModel {
+    pub prefix: Vec<u8>,
+    pub pallet: String,
+    pub item: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct StorageKeyModel {
+    pub key: Vec<u8>,
+    pub prefix: Vec<u8>,
+    pub args: serde_json::Value,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromRow)]
 pub struct BestBlockModel {
     pub block_num: u32,
@@ -52,3 +119,23 @@ pub struct FinalizedBlockModel {
     pub block_num: u32,
     pub block_hash: Vec<u8>,
 }
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct FailedBlockModel {
+    pub block_num: u32,
+    pub attempts: u32,
+    pub error: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct OutboxModel {
+    pub kind: String,
+    pub key: Option<String>,
+    pub payload: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct OutboxOffsetModel {
+    pub sink: String,
+    pub last_id: i64,
+}
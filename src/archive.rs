@@ -1,4 +1,11 @@
-use std::{marker::PhantomData, str::FromStr, sync::Arc, time::Instant};
+use std::{
+    marker::PhantomData,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures::future::{self, Either, FutureExt};
 
 use sc_chain_spec::ChainSpec;
 use sc_client_api::backend::{Backend, StateBackendFor};
@@ -7,7 +14,7 @@ use sp_api::{ApiExt, BlockId, ConstructRuntimeApi, Core as CoreApi, Metadata as
 use sp_blockchain::Backend as BlockchainBackend;
 use sp_runtime::traits::Block as BlockT;
 
-use archive_actor::{ActorConfig, Actors};
+use archive_actor::{ActorConfig, Actors, Supervisor};
 use archive_client::{new_backend, new_client, ApiAccess, ArchiveBackend, ArchiveClient};
 use archive_postgres::migrate;
 
@@ -17,6 +24,16 @@ pub trait Archive {
     /// start driving the execution of the archive.
     fn drive(&self) -> Result<(), ArchiveError>;
 
+    /// Returns a handle to kill the archive system, e.g. in the Ctrl-C handler.
+    fn kill_handle(&self) -> KillHandle;
+
+    /// Wait until the archive work loop finishes by itself (e.g. backfill or repair is done),
+    /// or is killed by the `KillHandle`.
+    fn wait(self) -> Result<(), ArchiveError>;
+
+    /// Wait for the archive system when self is boxed (useful when erasing the types of the runtime).
+    fn boxed_wait(self: Box<Self>) -> Result<(), ArchiveError>;
+
     /// shutdown the archive system.
     fn shutdown(self) -> Result<(), ArchiveError>;
 
@@ -24,6 +41,17 @@ pub trait Archive {
     fn boxed_shutdown(self: Box<Self>) -> Result<(), ArchiveError>;
 }
 
+/// The handle to kill the archive system, the in-flight blocks are drained before exiting.
+#[derive(Clone)]
+pub struct KillHandle(flume::Sender<()>);
+
+impl KillHandle {
+    pub fn kill(&self) {
+        // the kill signal may have been sent, it's fine.
+        let _ = self.0.try_send(());
+    }
+}
+
 pub struct ArchiveSystem<Block, Client, RA> {
     start_tx: flume::Sender<()>,
     kill_tx: flume::Sender<()>,
@@ -56,8 +84,7 @@ where
         let handle = jod_thread::spawn(move || {
             start_rx.recv().expect("Start Archive Work Loop");
             log::info!(target: "archive", "Start Archive Work Loop");
-            runtime.block_on(Self::work(backend, client, config, kill_rx))?;
-            Ok(())
+            runtime.block_on(Self::work(backend, client, config, kill_rx))
         });
 
         Ok(Self {
@@ -73,25 +100,64 @@ where
         Ok(())
     }
 
-    fn shutdown(self) -> Result<(), ArchiveError> {
-        self.kill_tx.send(())?;
+    fn kill_handle(&self) -> KillHandle {
+        KillHandle(self.kill_tx.clone())
+    }
+
+    fn wait(self) -> Result<(), ArchiveError> {
         self.handle.join()?;
         Ok(())
     }
 
+    fn shutdown(self) -> Result<(), ArchiveError> {
+        self.kill_handle().kill();
+        self.wait()
+    }
+
     async fn work(
         backend: Arc<ArchiveBackend<Block>>,
         client: Arc<Client>,
         config: ActorConfig,
         kill_rx: flume::Receiver<()>,
     ) -> Result<(), ArchiveError> {
+        let backfill = config.scheduler.backfill;
+        let repair = config.scheduler.repair;
+        let drain_timeout = Duration::from_millis(config.supervisor.drain_timeout_ms);
+        if backfill.is_none() && repair.is_none() {
+            // follow the chain until the kill signal is received.
+            log::info!(target: "archive", "Spawn All Actors");
+            let shutdown = kill_rx.recv_async().map(|_| ());
+            Supervisor::new(backend, client, config)
+                .run(shutdown)
+                .await?;
+            return Ok(());
+        }
+
         log::info!(target: "archive", "Spawn All Actors");
         let actors = Actors::spawn(backend, client, config).await?;
-        actors.tick_interval().await?;
-        // waiting until the kill signal is received.
-        let _ = kill_rx.recv_async().await;
+        if let Some(backfill) = backfill {
+            // waiting until the backfill is done or the kill signal is received.
+            let done = Box::pin(actors.backfill(backfill.from, backfill.to));
+            match future::select(done, kill_rx.recv_async()).await {
+                Either::Left((result, _)) => result?,
+                Either::Right(_) => {
+                    log::warn!(target: "archive", "Backfill is interrupted");
+                    actors.drain(drain_timeout).await;
+                }
+            }
+        } else if let Some(repair) = repair {
+            // waiting until the repair is done or the kill signal is received.
+            let done = Box::pin(actors.repair(repair.from, repair.to));
+            match future::select(done, kill_rx.recv_async()).await {
+                Either::Left((result, _)) => result?,
+                Either::Right(_) => {
+                    log::warn!(target: "archive", "Repair is interrupted");
+                    actors.drain(drain_timeout).await;
+                }
+            }
+        }
         log::info!(target: "archive", "Stopping All Actors");
-        actors.kill().await?;
+        actors.kill(drain_timeout).await?;
         log::info!(target: "archive", "Stopped All Actors");
         Ok(())
     }
@@ -111,6 +177,18 @@ where
         Ok(())
     }
 
+    fn kill_handle(&self) -> KillHandle {
+        ArchiveSystem::kill_handle(self)
+    }
+
+    fn wait(self) -> Result<(), ArchiveError> {
+        ArchiveSystem::wait(self)
+    }
+
+    fn boxed_wait(self: Box<Self>) -> Result<(), ArchiveError> {
+        self.wait()
+    }
+
     fn shutdown(self) -> Result<(), ArchiveError> {
         let now = Instant::now();
         ArchiveSystem::shutdown(self)?;
@@ -168,6 +246,8 @@ where
                 postgres: self.config.postgres,
                 dispatcher: self.config.dispatcher,
                 scheduler: self.config.scheduler,
+                supervisor: self.config.supervisor,
+                mailbox: self.config.mailbox,
             },
         )?;
         Ok(system)
@@ -3,7 +3,10 @@ use std::{fs, path::PathBuf};
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
-use archive_actor::{DispatcherConfig, PostgresConfig, SchedulerConfig};
+use archive_actor::{
+    BackfillConfig, DispatcherConfig, MailboxConfig, PostgresConfig, RepairConfig, SchedulerConfig,
+    SupervisorConfig,
+};
 use archive_client::ClientConfig;
 
 use crate::{error::ArchiveError, logger::LoggerConfig};
@@ -15,6 +18,10 @@ pub struct ArchiveConfig {
     pub(crate) postgres: PostgresConfig,
     pub(crate) dispatcher: Option<DispatcherConfig>,
     pub(crate) scheduler: SchedulerConfig,
+    #[serde(default)]
+    pub(crate) supervisor: SupervisorConfig,
+    #[serde(default)]
+    pub(crate) mailbox: MailboxConfig,
 }
 
 #[derive(Clone, Debug, StructOpt)]
@@ -28,6 +35,32 @@ pub struct ArchiveCli {
     /// NOTE: you need to know what you are doing!!!
     #[structopt(long, name = "NUM")]
     start_block: Option<u32>,
+
+    #[structopt(subcommand)]
+    command: Option<ArchiveCommand>,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum ArchiveCommand {
+    /// Archives the blocks within the range [from, to] and exits,
+    /// the blocks outside the range are not touched.
+    Backfill {
+        /// Specifies the first block number of the range.
+        #[structopt(long)]
+        from: u32,
+        /// Specifies the last block number of the range.
+        #[structopt(long)]
+        to: u32,
+    },
+    /// Re-crawls the blocks that are missing or have no main storage and exits.
+    Repair {
+        /// Specifies the first block number to check, defaults to the genesis block.
+        #[structopt(long)]
+        from: Option<u32>,
+        /// Specifies the last block number to check, defaults to the latest archived finalized block.
+        #[structopt(long)]
+        to: Option<u32>,
+    },
 }
 
 impl ArchiveCli {
@@ -36,6 +69,15 @@ impl ArchiveCli {
         let toml_str = fs::read_to_string(cli.config.as_path())?;
         let mut config = toml::from_str::<ArchiveConfig>(toml_str.as_str())?;
         config.scheduler.start_block = cli.start_block.or(config.scheduler.start_block);
+        match cli.command {
+            Some(ArchiveCommand::Backfill { from, to }) => {
+                config.scheduler.backfill = Some(BackfillConfig { from, to });
+            }
+            Some(ArchiveCommand::Repair { from, to }) => {
+                config.scheduler.repair = Some(RepairConfig { from, to });
+            }
+            None => {}
+        }
 
         // initialize the logger
         config.logger.clone().init()?;
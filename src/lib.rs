@@ -4,8 +4,8 @@ mod error;
 mod logger;
 
 pub use self::{
-    archive::{Archive, ArchiveSystem, ArchiveSystemBuilder},
-    cli::{ArchiveCli, ArchiveConfig},
+    archive::{Archive, ArchiveSystem, ArchiveSystemBuilder, KillHandle},
+    cli::{ArchiveCli, ArchiveCommand, ArchiveConfig},
     error::ArchiveError,
     logger::{FileLoggerConfig, LoggerConfig},
 };